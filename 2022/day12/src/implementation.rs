@@ -1,6 +1,10 @@
-use std::{collections::HashSet, hash::Hash};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BinaryHeap, HashMap, HashSet},
+    hash::Hash,
+};
 
-#[derive(Default, PartialEq, Eq, Hash, Debug, Clone, Copy)]
+#[derive(Default, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
 struct Coord {
     x: usize,
     y: usize,
@@ -10,23 +14,95 @@ impl Coord {
     fn new(x: usize, y: usize) -> Self {
         Coord { x, y }
     }
+
+    fn offset(&self, (dx, dy): (isize, isize)) -> Option<Coord> {
+        Some(Coord::new(
+            self.x.checked_add_signed(dx)?,
+            self.y.checked_add_signed(dy)?,
+        ))
+    }
+}
+
+trait MoveRules {
+    // Offsets of the squares that can be reached in a single step
+    fn moves(&self) -> Vec<(isize, isize)>;
+    // Cost of stepping from a square to another one, None if the step is not allowed
+    fn step_cost(&self, from: char, to: char) -> Option<usize>;
+    // Lower bound of the cost needed to travel between two squares, used to guide the search
+    fn estimate(&self, from: Coord, to: Coord) -> usize;
+}
+
+struct ClimbingRules {
+    max_climb: usize,
+    max_descent: usize,
+    diagonal: bool,
+    cost_per_elevation: usize,
+    impassable: HashSet<char>,
+}
+
+impl Default for ClimbingRules {
+    fn default() -> Self {
+        ClimbingRules {
+            max_climb: 1,
+            max_descent: usize::MAX,
+            diagonal: false,
+            cost_per_elevation: 0,
+            impassable: HashSet::new(),
+        }
+    }
+}
+
+impl MoveRules for ClimbingRules {
+    fn moves(&self) -> Vec<(isize, isize)> {
+        let mut result = vec![(-1, 0), (1, 0), (0, -1), (0, 1)];
+        if self.diagonal {
+            result.extend([(-1, -1), (1, -1), (-1, 1), (1, 1)]);
+        }
+        result
+    }
+
+    fn step_cost(&self, from: char, to: char) -> Option<usize> {
+        if self.impassable.contains(&from) || self.impassable.contains(&to) {
+            return None;
+        }
+        let (from, to) = (from as usize, to as usize);
+        if to > from && to - from > self.max_climb || from > to && from - to > self.max_descent {
+            return None;
+        }
+        Some(1 + self.cost_per_elevation * from.abs_diff(to))
+    }
+
+    fn estimate(&self, from: Coord, to: Coord) -> usize {
+        let (dx, dy) = (from.x.abs_diff(to.x), from.y.abs_diff(to.y));
+        if self.diagonal {
+            dx.max(dy)
+        } else {
+            dx + dy
+        }
+    }
 }
 
-#[derive(Default)]
 struct Grid {
     squares: Vec<Vec<char>>,
     src: Coord,
     dst: Coord,
+    rules: Box<dyn MoveRules>,
 }
 
 impl Grid {
     fn new(input: &str) -> Self {
+        Grid::with_rules(input, ClimbingRules::default())
+    }
+
+    fn with_rules(input: &str, rules: impl MoveRules + 'static) -> Self {
         let mut result = Grid {
             squares: input
                 .lines()
                 .map(|x| x.chars().collect::<Vec<char>>())
                 .collect(),
-            ..Default::default()
+            src: Coord::default(),
+            dst: Coord::default(),
+            rules: Box::new(rules),
         };
         for (j, line) in result.squares.iter_mut().enumerate() {
             for (i, c) in line.iter_mut().enumerate() {
@@ -42,90 +118,149 @@ impl Grid {
         result
     }
 
-    fn reachable(&self, c: Coord, n: Coord) -> bool {
-        self.squares[c.y][c.x] as usize + 1 >= self.squares[n.y][n.x] as usize
+    fn is_in_bound(&self, c: Coord) -> bool {
+        c.y < self.squares.len() && c.x < self.squares[c.y].len()
     }
 
-    fn get_neigh_coord_raw(&self, c: Coord) -> Vec<Coord> {
-        let mut result = vec![];
-        if c.x > 0 {
-            let n = Coord::new(c.x - 1, c.y);
-            if self.reachable(c, n) {
-                result.push(n);
-            }
-        }
-        if c.x < self.squares[0].len() - 1 {
-            let n = Coord::new(c.x + 1, c.y);
-            if self.reachable(c, n) {
-                result.push(n);
+    fn get_step_cost(&self, c: Coord, n: Coord) -> Option<usize> {
+        self.rules
+            .step_cost(self.squares[c.y][c.x], self.squares[n.y][n.x])
+    }
+
+    fn get_neigh_coord_raw(&self, c: Coord) -> Vec<(Coord, usize)> {
+        self.rules
+            .moves()
+            .into_iter()
+            .filter_map(|m| c.offset(m))
+            .filter(|n| self.is_in_bound(*n))
+            .filter_map(|n| self.get_step_cost(c, n).map(|cost| (n, cost)))
+            .collect()
+    }
+
+    fn get_dist_to_dst(&self, c: Coord) -> usize {
+        self.rules.estimate(c, self.dst)
+    }
+
+    // A* search : with a consistent estimate, a square popped from the heap has its final cost
+    fn search(&self, start: Coord, threshold: Option<usize>) -> (usize, HashMap<Coord, Coord>) {
+        let mut open = BinaryHeap::new();
+        let mut costs = HashMap::from([(start, 0)]);
+        let mut parents = HashMap::new();
+        open.push(Reverse((self.get_dist_to_dst(start), 0, start)));
+        while let Some(Reverse((h, cost, candidate))) = open.pop() {
+            if cost > costs[&candidate] {
+                continue;
             }
-        }
-        if c.y > 0 {
-            let n = Coord::new(c.x, c.y - 1);
-            if self.reachable(c, n) {
-                {
-                    result.push(n);
+            if candidate == self.dst {
+                return (cost, parents);
+            };
+            if let Some(x) = threshold {
+                if h >= x {
+                    return (usize::MAX, parents);
                 }
             }
-        }
-        if c.y < self.squares.len() - 1 {
-            let n = Coord::new(c.x, c.y + 1);
-            if self.reachable(c, n) {
-                {
-                    result.push(n);
+            for (n, step) in self.get_neigh_coord_raw(candidate) {
+                let cost = cost + step;
+                if costs.get(&n).is_some_and(|&x| x <= cost) {
+                    continue;
                 }
+                costs.insert(n, cost);
+                parents.insert(n, candidate);
+                open.push(Reverse((cost + self.get_dist_to_dst(n), cost, n)));
             }
         }
-        result
+        (0, parents)
     }
 
-    fn get_dist_to_dst(&self, c: Coord) -> u64 {
-        ((c.x.abs_diff(self.dst.x).pow(2) + c.y.abs_diff(self.dst.y).pow(2)) as f64).sqrt() as u64
+    fn get_cheapest_path(&self, start: Coord, threshold: Option<usize>) -> usize {
+        self.search(start, threshold).0
     }
 
-    fn get_cheapest_path(&self, start: Coord, threshold: Option<usize>) -> usize {
-        let mut open = HashSet::new();
-        let mut close = open.clone();
-        open.insert((start, 0, 0));
-        while !open.is_empty() {
-            let candidate = open
-                .iter()
-                .cloned()
-                .reduce(|acc, item| if acc.2 < item.2 { acc } else { item })
-                .unwrap();
-            open.remove(&candidate);
-            if candidate.0 == self.dst {
-                return candidate.1;
+    fn get_route(&self, start: Coord) -> Option<Vec<Coord>> {
+        let (_, parents) = self.search(start, None);
+        let mut route = vec![self.dst];
+        let mut current = self.dst;
+        while current != start {
+            current = *parents.get(&current)?;
+            route.push(current);
+        }
+        route.reverse();
+        Some(route)
+    }
+
+    fn render_route(&self, route: &[Coord]) -> String {
+        let mut cells = vec![vec!['.'; self.squares[0].len()]; self.squares.len()];
+        for step in route.windows(2) {
+            let (a, b) = (step[0], step[1]);
+            cells[a.y][a.x] = match (b.x.cmp(&a.x), b.y.cmp(&a.y)) {
+                (Ordering::Greater, Ordering::Equal) => '>',
+                (Ordering::Less, Ordering::Equal) => '<',
+                (Ordering::Equal, Ordering::Greater) => 'v',
+                (Ordering::Equal, _) => '^',
+                (Ordering::Greater, Ordering::Greater) | (Ordering::Less, Ordering::Less) => '\\',
+                _ => '/',
             };
-            if let Some(x) = threshold {
-                if candidate.1 >= x {
-                    return usize::MAX;
-                }
+        }
+        if let Some(last) = route.last() {
+            cells[last.y][last.x] = 'E';
+        }
+        cells
+            .iter()
+            .map(|line| line.iter().collect::<String>())
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    // Cost needed to reach the destination from each square, None if it can not be reached
+    fn get_distance_field(&self) -> Vec<Vec<Option<usize>>> {
+        let mut field = vec![vec![None; self.squares[0].len()]; self.squares.len()];
+        let mut open = BinaryHeap::from([Reverse((0, self.dst))]);
+        while let Some(Reverse((dist, c))) = open.pop() {
+            if field[c.y][c.x].is_some() {
+                continue;
             }
-            for n in self.get_neigh_coord_raw(candidate.0) {
-                if close.iter().cloned().any(|x| x.0 == n) {
+            field[c.y][c.x] = Some(dist);
+            for (dx, dy) in self.rules.moves() {
+                let Some(n) = c.offset((-dx, -dy)) else {
                     continue;
-                }
-                let cost = candidate.1 + 1;
-                if open.iter().cloned().any(|x| x.0 == n && x.1 < cost) {
+                };
+                if !self.is_in_bound(n) || field[n.y][n.x].is_some() {
                     continue;
                 }
-                let h = cost as u64 + self.get_dist_to_dst(n);
-                open.insert((n, cost, h));
+                if let Some(step) = self.get_step_cost(n, c) {
+                    open.push(Reverse((dist + step, n)));
+                }
             }
-            close.insert(candidate);
         }
-        0
+        field
     }
-}
 
-pub fn part_1(input: &str) -> usize {
-    let grid = Grid::new(input);
-    grid.get_cheapest_path(grid.src, None)
+    fn render_distance_field(&self) -> String {
+        let field = self.get_distance_field();
+        let width = field
+            .iter()
+            .flatten()
+            .flatten()
+            .max()
+            .map_or(1, |x| x.to_string().len());
+        field
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|d| match d {
+                        Some(d) => format!("{d:>width$}"),
+                        None => format!("{:>width$}", "."),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
 }
 
-pub fn part_2(input: &str) -> usize {
-    let grid = Grid::new(input);
+// Start square at elevation 'a' with the shortest path to E, and the length of that path
+fn best_start(grid: &Grid) -> (Coord, usize) {
     let mut candidates = vec![(grid.src, grid.get_dist_to_dst(grid.src))];
     for (j, line) in grid.squares.iter().enumerate() {
         for (i, c) in line.iter().enumerate() {
@@ -138,6 +273,7 @@ pub fn part_2(input: &str) -> usize {
     
     // Check all candidates : pick best choice, compute path, stop if costs under computation is higher than minimal path found
     let mut shortest = grid.get_cheapest_path(grid.src, Some(usize::MAX));
+    let mut best_start = grid.src;
     while !candidates.is_empty() {
         let (index, _) = candidates
             .iter()
@@ -146,12 +282,40 @@ pub fn part_2(input: &str) -> usize {
             .unwrap();
         let candidate = candidates.swap_remove(index).0;
         let path_cost = grid.get_cheapest_path(candidate, Some(shortest));
-        println!("Cost for {candidate:?} : {path_cost}.\t {} remanining, shortest is {shortest}", candidates.len());
         if path_cost > 0 && path_cost < shortest {
             shortest = path_cost;
+            best_start = candidate;
         }
     }
-    shortest
+    (best_start, shortest)
+}
+
+// Routes from S and from the best start, drawn over the heightmap
+pub fn render_routes(input: &str) -> String {
+    let grid = Grid::new(input);
+    let (start, _) = best_start(&grid);
+    [grid.src, start]
+        .iter()
+        .map(|&from| {
+            grid.get_route(from)
+                .map_or(String::from("No route"), |route| grid.render_route(&route))
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+pub fn render_distances(input: &str) -> String {
+    Grid::new(input).render_distance_field()
+}
+
+pub fn part_1(input: &str) -> usize {
+    let grid = Grid::new(input);
+    grid.get_cheapest_path(grid.src, None)
+}
+
+pub fn part_2(input: &str) -> usize {
+    let grid = Grid::new(input);
+    best_start(&grid).1
 }
 
 #[cfg(test)]
@@ -164,6 +328,9 @@ abcryxxl
 accszExk
 acctuvwj
 abdefghi";
+    const WEIGHTED_INPUT: &str = "\
+SakaE
+aaaaa";
 
     #[test]
     fn test_grid_parsing() {
@@ -172,6 +339,122 @@ abdefghi";
         assert_eq!(grid.dst, Coord::new(5, 2));
     }
 
+    #[test]
+    fn test_route() {
+        let grid = Grid::new(TEST_INPUT);
+        let route = grid.get_route(grid.src).unwrap();
+        assert_eq!(route.len(), 32);
+        assert_eq!(route.first(), Some(&grid.src));
+        assert_eq!(route.last(), Some(&grid.dst));
+        assert!(route
+            .windows(2)
+            .all(|s| grid.get_neigh_coord_raw(s[0]).contains(&(s[1], 1))));
+    }
+
+    #[test]
+    fn test_render_route() {
+        let grid = Grid::new(TEST_INPUT);
+        let route = grid.get_route(grid.src).unwrap();
+        assert_eq!(route.len(), 32);
+        // Ties between shortest routes may be broken either way, so follow the drawn arrows back
+        let drawing = grid.render_route(&route);
+        let arrows: Vec<Vec<char>> = drawing.lines().map(|l| l.chars().collect()).collect();
+        let mut traced = vec![grid.src];
+        let mut c = grid.src;
+        while c != grid.dst {
+            c = match arrows[c.y][c.x] {
+                '>' => Coord::new(c.x + 1, c.y),
+                '<' => Coord::new(c.x - 1, c.y),
+                'v' => Coord::new(c.x, c.y + 1),
+                '^' => Coord::new(c.x, c.y - 1),
+                x => panic!("Unexpected {x} on the route"),
+            };
+            traced.push(c);
+        }
+        assert_eq!(traced, route);
+        assert_eq!(drawing.chars().filter(|&x| x == '.').count(), 40 - 32);
+        assert_eq!(drawing.lines().nth(2).unwrap().find('E'), Some(5));
+        let (start, length) = best_start(&grid);
+        assert_eq!(grid.get_route(start).unwrap().len(), length + 1);
+    }
+
+    #[test]
+    fn test_distance_field() {
+        let grid = Grid::new(TEST_INPUT);
+        let field = grid.get_distance_field();
+        assert_eq!(field[grid.src.y][grid.src.x], Some(31));
+        assert_eq!(field[grid.dst.y][grid.dst.x], Some(0));
+        for (j, line) in field.iter().enumerate() {
+            for (i, d) in line.iter().enumerate() {
+                if let Some(d) = d {
+                    assert!(grid.get_dist_to_dst(Coord::new(i, j)) <= *d);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_diagonal_moves() {
+        let rules = ClimbingRules {
+            diagonal: true,
+            ..Default::default()
+        };
+        let grid = Grid::with_rules(TEST_INPUT, rules);
+        let route = grid.get_route(grid.src).unwrap();
+        assert!(route.len() < 32);
+        assert_eq!(route.len() - 1, grid.get_cheapest_path(grid.src, None));
+    }
+
+    #[test]
+    fn test_limited_descent() {
+        let rules = ClimbingRules {
+            max_descent: 0,
+            ..Default::default()
+        };
+        let grid = Grid::with_rules("Sbcba\nbbcdE", rules);
+        assert_eq!(grid.get_cheapest_path(grid.src, None), 0);
+        assert_eq!(grid.get_route(grid.src), None);
+    }
+
+    #[test]
+    fn test_impassable_squares() {
+        let rules = ClimbingRules {
+            impassable: HashSet::from(['#']),
+            max_climb: 25,
+            ..Default::default()
+        };
+        let grid = Grid::with_rules("Sa#aE\naaaaa", rules);
+        assert_eq!(grid.get_cheapest_path(grid.src, None), 6);
+        assert!(!grid
+            .get_route(grid.src)
+            .unwrap()
+            .contains(&Coord::new(2, 0)));
+    }
+
+    #[test]
+    fn test_weighted_costs() {
+        // Climbing over the bump is shorter but costs more than the detour around it
+        let rules = ClimbingRules {
+            cost_per_elevation: 10,
+            max_climb: 25,
+            ..Default::default()
+        };
+        let grid = Grid::with_rules(WEIGHTED_INPUT, rules);
+        let route = grid.get_route(grid.src).unwrap();
+        let cost = grid.get_cheapest_path(grid.src, None);
+        assert_eq!(route.len() - 1, 6);
+        assert_eq!(cost, 6 + 10 * 25);
+        assert_eq!(
+            route
+                .windows(2)
+                .map(|s| grid.get_step_cost(s[0], s[1]).unwrap())
+                .sum::<usize>(),
+            cost
+        );
+        let field = grid.get_distance_field();
+        assert_eq!(field[grid.src.y][grid.src.x], Some(cost));
+    }
+
     #[test]
     fn test_part_1() {
         // Adjust part 1 test to match provided challenge example
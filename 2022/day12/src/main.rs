@@ -3,6 +3,14 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| args.iter().any(|x| x == name);
+    if flag("--route") {
+        println!("{}", render_routes(input));
+    } else if flag("--distances") {
+        println!("{}", render_distances(input));
+    } else {
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+    }
 }
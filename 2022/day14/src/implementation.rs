@@ -10,6 +10,8 @@ enum CellKind {
     Empty,
     Rock,
     Sand,
+    Water,
+    Flow,
 }
 
 #[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
@@ -24,28 +26,179 @@ impl From<(usize, usize)> for Coord {
     }
 }
 
+// Bresenham line between two points, covering horizontal, vertical and diagonal segments
 fn rocks(a: &Coord, b: &Coord) -> Vec<Coord> {
-    if a.x == b.x {
-        (cmp::min(a.y, b.y)..=cmp::max(a.y, b.y))
-            .map(|y| Coord::from((a.x, y)))
-            .collect()
-    } else if a.y == b.y {
-        (cmp::min(a.x, b.x)..=cmp::max(a.x, b.x))
-            .map(|x| Coord::from((x, a.y)))
-            .collect()
-    } else {
-        panic!("I can not draw lines that are not horizontal nor vertical");
+    let (dx, dy) = (a.x.abs_diff(b.x) as isize, -(a.y.abs_diff(b.y) as isize));
+    let (sx, sy) = (
+        if a.x < b.x { 1 } else { -1 },
+        if a.y < b.y { 1 } else { -1 },
+    );
+    let (mut x, mut y) = (a.x as isize, a.y as isize);
+    let mut err = dx + dy;
+    let mut result = vec![];
+    loop {
+        result.push(Coord::from((x as usize, y as usize)));
+        if x == b.x as isize && y == b.y as isize {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+    if a.x > b.x || (a.x == b.x && a.y > b.y) {
+        result.reverse();
+    }
+    result
+}
+
+// Cells strictly inside a closed path, using the even-odd rule on cell centers
+fn polygon_inside(path: &[Coord]) -> Vec<Coord> {
+    let x_range = path.iter().map(|c| c.x).min().unwrap()..=path.iter().map(|c| c.x).max().unwrap();
+    let y_range = path.iter().map(|c| c.y).min().unwrap()..=path.iter().map(|c| c.y).max().unwrap();
+    let mut result = vec![];
+    for y in y_range {
+        for x in x_range.clone() {
+            let (px, py) = (x as f64, y as f64);
+            let inside = path
+                .windows(2)
+                .filter(|e| {
+                    let (ax, ay, bx, by) =
+                        (e[0].x as f64, e[0].y as f64, e[1].x as f64, e[1].y as f64);
+                    (ay > py) != (by > py) && px < ax + (py - ay) * (bx - ax) / (by - ay)
+                })
+                .count()
+                % 2
+                == 1;
+            if inside {
+                result.push(Coord::from((x, y)));
+            }
+        }
+    }
+    result
+}
+
+trait CellSet: Default {
+    fn with_bounds(x_min: usize, x_max: usize, y_max: usize) -> Self;
+    fn contains(&self, c: &Coord) -> bool;
+    fn insert(&mut self, c: Coord) -> bool;
+    fn len(&self) -> usize;
+}
+
+impl CellSet for HashSet<Coord> {
+    fn with_bounds(_x_min: usize, _x_max: usize, _y_max: usize) -> Self {
+        HashSet::new()
+    }
+
+    fn contains(&self, c: &Coord) -> bool {
+        HashSet::contains(self, c)
+    }
+
+    fn insert(&mut self, c: Coord) -> bool {
+        HashSet::insert(self, c)
+    }
+
+    fn len(&self) -> usize {
+        HashSet::len(self)
+    }
+}
+
+// Dense set of cells, one bit per cell, growing when a cell is inserted out of its bounds
+#[derive(Default)]
+struct BitGrid {
+    bits: Vec<u64>,
+    x_min: usize,
+    width: usize,
+    height: usize,
+    count: usize,
+}
+
+impl BitGrid {
+    fn index(&self, c: &Coord) -> Option<usize> {
+        if c.x < self.x_min || c.x >= self.x_min + self.width || c.y >= self.height {
+            None
+        } else {
+            Some(c.y * self.width + c.x - self.x_min)
+        }
+    }
+
+    fn grow(&mut self, c: &Coord) {
+        let x_min = cmp::min(self.x_min, c.x);
+        let x_max = cmp::max(self.x_min + self.width, c.x + 1) - 1;
+        let y_max = cmp::max(self.height, c.y + 1) - 1;
+        let mut grown = BitGrid::with_bounds(x_min, x_max, y_max);
+        for y in 0..self.height {
+            for x in self.x_min..self.x_min + self.width {
+                let old = Coord::from((x, y));
+                if self.contains(&old) {
+                    grown.insert(old);
+                }
+            }
+        }
+        *self = grown;
+    }
+}
+
+impl CellSet for BitGrid {
+    fn with_bounds(x_min: usize, x_max: usize, y_max: usize) -> Self {
+        let (width, height) = (x_max - x_min + 1, y_max + 1);
+        BitGrid {
+            bits: vec![0; (width * height).div_ceil(64)],
+            x_min,
+            width,
+            height,
+            count: 0,
+        }
+    }
+
+    fn contains(&self, c: &Coord) -> bool {
+        self.index(c)
+            .is_some_and(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    fn insert(&mut self, c: Coord) -> bool {
+        if self.index(&c).is_none() {
+            self.grow(&c);
+        }
+        let i = self.index(&c).unwrap();
+        let new = self.bits[i / 64] & (1 << (i % 64)) == 0;
+        self.bits[i / 64] |= 1 << (i % 64);
+        if new {
+            self.count += 1;
+        }
+        new
+    }
+
+    fn len(&self) -> usize {
+        self.count
     }
 }
 
 #[derive(Default)]
-struct Cave {
-    rocks: HashSet<Coord>,
-    sand: HashSet<Coord>,
+struct Cave<S: CellSet = BitGrid> {
+    rocks: S,
+    sand: S,
+    // Every cell reached by water, and the ones where it settled
+    flow: S,
+    water: S,
     x_min: usize,
     x_max: usize,
     y_max: usize,
     floored: bool,
+    floor_offset: usize,
+    sources: Vec<Source>,
+}
+
+#[derive(Clone, Copy)]
+struct Source {
+    at: Coord,
+    // Maximum number of grains poured by this source, unlimited if None
+    budget: Option<usize>,
 }
 
 fn parse_rocks(input: &str) -> HashSet<Coord> {
@@ -58,40 +211,51 @@ fn parse_rocks(input: &str) -> HashSet<Coord> {
     )(input)
     .unwrap()
     .1;
-    coords
+    let mut result = coords
         .windows(2)
         .flat_map(|c| rocks(&c[0], &c[1]))
-        .collect::<HashSet<Coord>>()
+        .collect::<HashSet<Coord>>();
+    // A path ending where it started is a polygon, filled with rock
+    if coords.len() > 3 && coords.first() == coords.last() {
+        result.extend(polygon_inside(&coords));
+    }
+    result
 }
 
-impl Cave {
+impl<S: CellSet> Cave<S> {
     const SAND_SOURCE_X: usize = 500;
     const SAND_SOURCE_Y: usize = 0;
+    const FLOOR_OFFSET: usize = 2;
     fn new(input: &str) -> Self {
+        let rocks = input.lines().flat_map(parse_rocks).collect::<Vec<_>>();
+        let x_min = rocks.iter().map(|c| c.x).min().unwrap();
+        let x_max = rocks.iter().map(|c| c.x).max().unwrap();
+        let y_max = rocks.iter().map(|c| c.y).max().unwrap();
+        // Leave room for the sand triangle which builds up under the source once the cave is floored
+        let floor = y_max + Self::FLOOR_OFFSET;
+        let (grid_x_min, grid_x_max) = (
+            cmp::min(x_min, Self::SAND_SOURCE_X.saturating_sub(floor)),
+            cmp::max(x_max, Self::SAND_SOURCE_X + floor),
+        );
         let mut cave = Cave {
-            rocks: input.lines().fold(HashSet::new(), |acc, l| {
-                acc.union(&parse_rocks(l)).copied().collect::<HashSet<_>>()
-            }),
-            ..Default::default()
+            rocks: S::with_bounds(grid_x_min, grid_x_max, floor),
+            sand: S::with_bounds(grid_x_min, grid_x_max, floor),
+            // Water may spill over the outermost rocks and fall beside them
+            flow: S::with_bounds(x_min.saturating_sub(1), x_max + 1, floor),
+            water: S::with_bounds(x_min.saturating_sub(1), x_max + 1, floor),
+            x_min,
+            x_max,
+            y_max,
+            floored: false,
+            floor_offset: Self::FLOOR_OFFSET,
+            sources: vec![Source {
+                at: Coord::from((Self::SAND_SOURCE_X, Self::SAND_SOURCE_Y)),
+                budget: None,
+            }],
         };
-        cave.x_min = cave
-            .rocks
-            .iter()
-            .reduce(|acc, item| if item.x < acc.x { item } else { acc })
-            .unwrap()
-            .x;
-        cave.x_max = cave
-            .rocks
-            .iter()
-            .reduce(|acc, item| if item.x > acc.x { item } else { acc })
-            .unwrap()
-            .x;
-        cave.y_max = cave
-            .rocks
-            .iter()
-            .reduce(|acc, item| if item.y > acc.y { item } else { acc })
-            .unwrap()
-            .y;
+        for rock in rocks {
+            cave.rocks.insert(rock);
+        }
         cave
     }
 
@@ -100,15 +264,151 @@ impl Cave {
     }
 
     fn get_cell_type(&self, c: Coord) -> CellKind {
-        if self.rocks.contains(&c) || (self.floored && c.y == self.y_max + 2) {
+        if self.rocks.contains(&c) || (self.floored && c.y == self.y_max + self.floor_offset) {
             CellKind::Rock
         } else if self.sand.contains(&c) {
             CellKind::Sand
+        } else if self.water.contains(&c) {
+            CellKind::Water
+        } else if self.flow.contains(&c) {
+            CellKind::Flow
         } else {
             CellKind::Empty
         }
     }
 
+    fn is_solid(&self, c: Coord) -> bool {
+        matches!(
+            self.get_cell_type(c),
+            CellKind::Rock | CellKind::Sand | CellKind::Water
+        )
+    }
+
+    fn is_in_water_bound(&self, c: &Coord) -> bool {
+        let y_max = if self.floored {
+            self.y_max + self.floor_offset - 1
+        } else {
+            self.y_max
+        };
+        c.x + 1 >= self.x_min && c.x <= self.x_max + 1 && c.y <= y_max
+    }
+
+    // Water spreads sideways over solid cells, returns whether it was stopped by a wall
+    fn spread_water(&mut self, c: Coord, left: bool) -> (bool, usize) {
+        let mut x = c.x;
+        loop {
+            if left && x == 0 {
+                return (false, x);
+            }
+            let next = Coord::from((if left { x - 1 } else { x + 1 }, c.y));
+            if self.is_solid(next) {
+                return (true, x);
+            }
+            if !self.is_in_water_bound(&next) {
+                return (false, x);
+            }
+            self.flow.insert(next);
+            let below = self.get_down_cell(&next);
+            if !self.is_solid(below) && !self.pour_water_from(below) {
+                return (false, next.x);
+            }
+            x = next.x;
+        }
+    }
+
+    // Water falls until it meets a solid cell, then fills the basin it lands in. Returns whether
+    // water settled in the given cell
+    fn pour_water_from(&mut self, c: Coord) -> bool {
+        if !self.is_in_water_bound(&c) {
+            return false;
+        }
+        if self.is_solid(c) {
+            return true;
+        }
+        if self.flow.contains(&c) {
+            return false;
+        }
+        self.flow.insert(c);
+        let below = self.get_down_cell(&c);
+        if !self.is_solid(below) && !self.pour_water_from(below) {
+            return false;
+        }
+        let (left_closed, left) = self.spread_water(c, true);
+        let (right_closed, right) = self.spread_water(c, false);
+        if left_closed && right_closed {
+            for x in left..=right {
+                self.water.insert(Coord::from((x, c.y)));
+            }
+            true
+        } else {
+            false
+        }
+    }
+
+    fn pour_water(&mut self) {
+        for source in self.sources.clone() {
+            self.pour_water_from(self.get_down_cell(&source.at));
+        }
+    }
+
+    // Once floored, every cell reachable from a source through the down, down-left and down-right
+    // moves ends up filled with sand. Reachable cells are tracked row by row as sorted intervals,
+    // split around rocks, so that the count does not need to simulate each grain. Sources are
+    // assumed to pour until they are blocked
+    fn floored_sand_count(&self) -> usize {
+        let mut count = 0;
+        let mut row: Vec<(isize, isize)> = vec![];
+        for y in 0..self.y_max + self.floor_offset {
+            let mut next: Vec<(isize, isize)> = vec![];
+            let mut expanded = row
+                .iter()
+                .map(|(a, b)| (a - 1, b + 1))
+                .chain(
+                    self.sources
+                        .iter()
+                        .filter(|s| s.at.y == y)
+                        .map(|s| (s.at.x as isize, s.at.x as isize)),
+                )
+                .collect::<Vec<_>>();
+            expanded.sort();
+            for (a, b) in expanded {
+                match next.last_mut() {
+                    Some(last) if a <= last.1 + 1 => last.1 = cmp::max(last.1, b),
+                    _ => next.push((a, b)),
+                }
+            }
+            if y <= self.y_max {
+                next = next
+                    .into_iter()
+                    .flat_map(|interval| self.split_on_rocks(interval, y))
+                    .collect();
+            }
+            count += next
+                .iter()
+                .map(|(a, b)| (b - a + 1) as usize)
+                .sum::<usize>();
+            row = next;
+        }
+        count
+    }
+
+    fn split_on_rocks(&self, (a, b): (isize, isize), y: usize) -> Vec<(isize, isize)> {
+        let mut result = vec![];
+        let mut start = a;
+        for x in cmp::max(a, self.x_min as isize)..=cmp::min(b, self.x_max as isize) {
+            if self.rocks.contains(&Coord::from((x as usize, y))) {
+                if start < x {
+                    result.push((start, x - 1));
+                }
+                start = x + 1;
+            }
+        }
+        if start <= b {
+            result.push((start, b));
+        }
+        result
+    }
+
     fn get_down_cell(&self, c: &Coord) -> Coord {
         Coord::from((c.x, c.y + 1))
     }
@@ -137,6 +437,8 @@ impl Cave {
         c
     }
 
+    // Naive drop restarting from the source, kept as a reference for the memoized grains
+    #[cfg(test)]
     fn drop_sand(&mut self) -> bool {
         let mut sand = Coord::from((Self::SAND_SOURCE_X, Self::SAND_SOURCE_Y));
         loop {
@@ -151,23 +453,112 @@ impl Cave {
             }
         }
     }
+
+    fn grains(&mut self) -> Grains<'_, S> {
+        Grains {
+            paths: self.sources.iter().map(|s| vec![s.at]).collect(),
+            dropped: vec![0; self.sources.len()],
+            next_source: 0,
+            cave: self,
+        }
+    }
+}
+
+// Drops grains from each source in turn, yielding their resting positions. The cells crossed by
+// the previous grain of a source are kept, so that its next grain resumes its fall from the last
+// free cell of that path
+struct Grains<'a, S: CellSet> {
+    cave: &'a mut Cave<S>,
+    paths: Vec<Vec<Coord>>,
+    dropped: Vec<usize>,
+    next_source: usize,
+}
+
+impl<S: CellSet> Grains<'_, S> {
+    fn fall(&mut self, source: usize) -> Option<Coord> {
+        let path = &mut self.paths[source];
+        loop {
+            let sand = *path.last()?;
+            let new_sand = self.cave.move_sand(sand);
+            if new_sand == sand {
+                self.cave.sand.insert(sand);
+                path.pop();
+                return Some(sand);
+            } else if !self.cave.floored && !self.cave.is_in_bound(&new_sand) {
+                // Every following grain would take the same way out of the cave
+                path.clear();
+                return None;
+            } else {
+                path.push(new_sand);
+            }
+        }
+    }
+
+    // A resting grain changes the way down of the cells right above it, so the other paths are
+    // cut at the first of those cells
+    fn invalidate(&mut self, grain: Coord) {
+        for path in self.paths.iter_mut() {
+            if let Some(i) = path
+                .iter()
+                .position(|c| c.y + 1 == grain.y && c.x.abs_diff(grain.x) <= 1 || *c == grain)
+            {
+                path.truncate(if path[i] == grain { i } else { i + 1 });
+            }
+        }
+    }
 }
 
-impl Display for Cave {
+impl<S: CellSet> Iterator for Grains<'_, S> {
+    type Item = Coord;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for _ in 0..self.paths.len() {
+            let source = self.next_source;
+            self.next_source = (source + 1) % self.paths.len();
+            if self.cave.sources[source]
+                .budget
+                .is_some_and(|b| self.dropped[source] >= b)
+            {
+                continue;
+            }
+            if let Some(grain) = self.fall(source) {
+                self.dropped[source] += 1;
+                self.invalidate(grain);
+                return Some(grain);
+            }
+        }
+        None
+    }
+}
+
+impl<S: CellSet> Display for Cave<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut result = String::new();
+        // Columns beside the outermost rocks are only drawn once water reached them
+        let reached = |x: usize| (0..=self.y_max).any(|y| self.flow.contains(&Coord::from((x, y))));
+        let x_min = match self.x_min.checked_sub(1) {
+            Some(x) if reached(x) => x,
+            _ => self.x_min,
+        };
+        let x_max = if reached(self.x_max + 1) {
+            self.x_max + 1
+        } else {
+            self.x_max
+        };
         for j in 0..=self.y_max {
             if j > 0 {
                 result.push('\n');
             }
-            for i in self.x_min..=self.x_max {
+            for i in x_min..=x_max {
                 if self.rocks.contains(&Coord::from((i, j))) {
                     result.push('#');
                 } else if self.sand.contains(&Coord::from((i, j))) {
                     result.push('o');
-                } else if Coord::from((i, j))
-                    == Coord::from((Cave::SAND_SOURCE_X, Cave::SAND_SOURCE_Y))
-                {
+                } else if self.water.contains(&Coord::from((i, j))) {
+                    result.push('~');
+                } else if self.flow.contains(&Coord::from((i, j))) {
+                    result.push('|');
+                } else if self.sources.iter().any(|s| s.at == Coord::from((i, j))) {
                     result.push('+');
                 } else {
                     result.push('.');
@@ -178,29 +569,37 @@ impl Display for Cave {
     }
 }
 
+fn fill_cave<S: CellSet>(input: &str, floored: bool) -> Cave<S> {
+    let mut cave = Cave::<S>::new(input);
+    cave.floored = floored;
+    cave.grains().count();
+    cave
+}
+
+// Cave drawing once water stopped flowing, with the cells reached by water and the ones where it
+// settled
+pub fn water(input: &str) -> (String, usize, usize) {
+    let mut cave = Cave::<BitGrid>::new(input);
+    cave.pour_water();
+    (cave.to_string(), cave.flow.len(), cave.water.len())
+}
+
 pub fn part_1(input: &str) -> usize {
-    let mut cave = Cave::new(input);
-    while !cave.drop_sand() {}
+    let cave = fill_cave::<BitGrid>(input, false);
     println!("{cave}");
     cave.sand.len()
 }
 
 pub fn part_2(input: &str) -> usize {
-    let mut cave = Cave::new(input);
+    let mut cave = Cave::<BitGrid>::new(input);
     cave.floored = true;
-    while !cave
-        .sand
-        .contains(&Coord::from((Cave::SAND_SOURCE_X, Cave::SAND_SOURCE_Y)))
-    {
-        cave.drop_sand();
-    }
-    println!("{cave}");
-    cave.sand.len()
+    cave.floored_sand_count()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
     mod test_data;
     use test_data::*;
 
@@ -230,22 +629,47 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_diagonal_line() {
+        let (a, b) = (Coord::from((3, 1)), Coord::from((1, 3)));
+        assert_eq!(
+            rocks(&a, &b),
+            vec![
+                Coord::from((1, 3)),
+                Coord::from((2, 2)),
+                Coord::from((3, 1))
+            ]
+        );
+        let (a, b) = (Coord::from((0, 0)), Coord::from((4, 2)));
+        assert_eq!(rocks(&a, &b).len(), 5);
+    }
+
+    #[test]
+    fn test_polygon() {
+        let rocks = parse_rocks("1,1 -> 5,1 -> 5,4 -> 1,4 -> 1,1");
+        assert_eq!(rocks.len(), 20);
+        let triangle = parse_rocks("0,0 -> 4,4 -> 0,4 -> 0,0");
+        assert_eq!(triangle.len(), 15);
+        assert!(triangle.contains(&Coord::from((1, 3))));
+        assert!(!triangle.contains(&Coord::from((3, 1))));
+    }
+
     #[test]
     fn test_load_cave() {
-        let cave = Cave::new(TEST_INPUT);
+        let cave = Cave::<BitGrid>::new(TEST_INPUT);
         assert_eq!(format!("{cave}"), TEST_OUTPUT_CAVE);
     }
 
     #[test]
     fn test_drop_one() {
-        let mut cave = Cave::new(TEST_INPUT);
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
         cave.drop_sand();
         assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_ONE_SAND);
     }
 
     #[test]
     fn test_drop_two() {
-        let mut cave = Cave::new(TEST_INPUT);
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
         cave.drop_sand();
         cave.drop_sand();
         assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_TWO_SAND);
@@ -253,13 +677,250 @@ mod tests {
 
     #[test]
     fn test_drop_five() {
-        let mut cave = Cave::new(TEST_INPUT);
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
         for _ in 0..5 {
             cave.drop_sand();
         }
         assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_FIVE_SAND);
     }
 
+    #[test]
+    fn test_grains() {
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
+        assert_eq!(
+            cave.grains().take(2).collect::<Vec<_>>(),
+            vec![Coord::from((500, 8)), Coord::from((499, 8))]
+        );
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_TWO_SAND);
+        assert_eq!(cave.grains().take(3).count(), 3);
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_FIVE_SAND);
+    }
+
+    #[test]
+    fn test_grains_match_drop_sand() {
+        for floored in [false, true] {
+            let mut dropped = Cave::<BitGrid>::new(TEST_INPUT);
+            dropped.floored = floored;
+            let mut memoized = Cave::<BitGrid>::new(TEST_INPUT);
+            memoized.floored = floored;
+            for grain in memoized.grains().collect::<Vec<_>>() {
+                assert!(!dropped.drop_sand());
+                assert!(dropped.sand.contains(&grain));
+            }
+            assert_eq!(dropped.sand.len(), memoized.sand.len());
+            assert_eq!(format!("{dropped}"), format!("{memoized}"));
+        }
+    }
+
+    #[test]
+    fn test_sources_budget() {
+        let mut cave = Cave::<BitGrid>::new("490,10 -> 510,10");
+        cave.sources = vec![
+            Source {
+                at: Coord::from((495, 0)),
+                budget: Some(3),
+            },
+            Source {
+                at: Coord::from((505, 0)),
+                budget: Some(2),
+            },
+        ];
+        assert_eq!(
+            cave.grains().collect::<Vec<_>>(),
+            vec![
+                Coord::from((495, 9)),
+                Coord::from((505, 9)),
+                Coord::from((494, 9)),
+                Coord::from((504, 9)),
+                Coord::from((496, 9))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sources_sharing_pile() {
+        let mut cave = Cave::<BitGrid>::new("490,10 -> 510,10");
+        cave.floored = true;
+        cave.sources = [498, 502]
+            .map(|x| Source {
+                at: Coord::from((x, 0)),
+                budget: None,
+            })
+            .to_vec();
+        let grains = cave.grains().collect::<Vec<_>>();
+        // Same simulation restarting every grain from its source
+        let mut naive = Cave::<BitGrid>::new("490,10 -> 510,10");
+        naive.floored = true;
+        let mut blocked = [false; 2];
+        let mut expected = vec![];
+        while !blocked.iter().all(|b| *b) {
+            for (i, x) in [498, 502].into_iter().enumerate() {
+                let mut sand = Coord::from((x, 0));
+                if blocked[i] || naive.sand.contains(&sand) {
+                    blocked[i] = true;
+                    continue;
+                }
+                while naive.move_sand(sand) != sand {
+                    sand = naive.move_sand(sand);
+                }
+                naive.sand.insert(sand);
+                expected.push(sand);
+            }
+        }
+        assert_eq!(grains, expected);
+    }
+
+    #[test]
+    fn test_floor_offset() {
+        let mut dropped = Cave::<BitGrid>::new(TEST_INPUT);
+        let mut memoized = Cave::<BitGrid>::new(TEST_INPUT);
+        for cave in [&mut dropped, &mut memoized] {
+            cave.floored = true;
+            cave.floor_offset = 4;
+        }
+        let count = memoized.grains().count();
+        while !dropped.sand.contains(&Coord::from((500, 0))) {
+            dropped.drop_sand();
+        }
+        assert_eq!(count, dropped.sand.len());
+        assert!(count > 93);
+        assert!(memoized.sand.contains(&Coord::from((490, 12))));
+        assert!(!memoized.sand.contains(&Coord::from((490, 13))));
+    }
+
+    #[test]
+    fn test_water() {
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT_RESERVOIR);
+        cave.pour_water();
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_RESERVOIR);
+        assert_eq!((cave.flow.len(), cave.water.len()), (57, 29));
+    }
+
+    #[test]
+    fn test_water_overflow() {
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
+        cave.pour_water();
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_WATER);
+        assert_eq!((cave.flow.len(), cave.water.len()), (17, 0));
+    }
+
+    #[test]
+    fn test_water_over_outer_walls() {
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT_OUTER_BASIN);
+        cave.pour_water();
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_OUTER_BASIN);
+        assert_eq!((cave.flow.len(), cave.water.len()), (48, 27));
+    }
+
+    #[test]
+    fn test_floored_sand_count() {
+        for offset in 1..6 {
+            let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
+            cave.floored = true;
+            cave.floor_offset = offset;
+            assert_eq!(cave.floored_sand_count(), cave.grains().count());
+        }
+    }
+
+    #[test]
+    fn test_floored_sand_count_sources() {
+        let mut cave = Cave::<BitGrid>::new(TEST_INPUT);
+        cave.floored = true;
+        cave.sources = [(497, 0), (500, 2), (510, 1)]
+            .map(|at| Source {
+                at: Coord::from(at),
+                budget: None,
+            })
+            .to_vec();
+        assert_eq!(cave.floored_sand_count(), cave.grains().count());
+    }
+
+    #[test]
+    fn test_floored_sand_count_generated() {
+        // Pseudo-random caves built from a linear congruential generator
+        let mut seed: usize = 42;
+        let mut random = |max: usize| {
+            seed = (seed * 1103515245 + 12345) % (1 << 31);
+            seed % max
+        };
+        for _ in 0..20 {
+            let input = (0..10)
+                .map(|_| {
+                    let (x, y) = (480 + random(40), 5 + random(40));
+                    let (w, h) = (random(8), random(4));
+                    format!("{x},{y} -> {},{y} -> {},{}", x + w, x + w, y + h)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            let mut cave = Cave::<BitGrid>::new(&input);
+            cave.floored = true;
+            assert_eq!(cave.floored_sand_count(), cave.grains().count());
+        }
+    }
+
+    #[test]
+    fn test_floored_sand_count_deep_cave() {
+        // A single rock right under the source only hides itself, the triangle fills the rest
+        let mut cave = Cave::<BitGrid>::new("500,20000 -> 500,20000");
+        cave.floored = true;
+        let floor = 20002;
+        assert_eq!(cave.floored_sand_count(), floor * floor - 1);
+    }
+
+    #[test]
+    fn test_hash_set_backend() {
+        let mut cave = Cave::<HashSet<Coord>>::new(TEST_INPUT);
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_CAVE);
+        for _ in 0..5 {
+            cave.drop_sand();
+        }
+        assert_eq!(format!("{cave}"), TEST_OUTPUT_DROP_FIVE_SAND);
+        assert_eq!(fill_cave::<HashSet<Coord>>(TEST_INPUT, true).sand.len(), 93);
+    }
+
+    #[test]
+    fn test_bit_grid_growth() {
+        let mut grid = BitGrid::with_bounds(10, 12, 2);
+        assert!(grid.insert(Coord::from((11, 1))));
+        assert!(!grid.insert(Coord::from((11, 1))));
+        assert!(grid.insert(Coord::from((3, 7))));
+        assert!(grid.insert(Coord::from((20, 0))));
+        assert!(grid.contains(&Coord::from((11, 1))));
+        assert!(grid.contains(&Coord::from((3, 7))));
+        assert!(grid.contains(&Coord::from((20, 0))));
+        assert!(!grid.contains(&Coord::from((12, 1))));
+        assert!(!grid.contains(&Coord::from((100, 100))));
+        assert_eq!(grid.len(), 3);
+    }
+
+    // Run with `cargo test --release -- --ignored --nocapture` to compare backends on the real input
+    #[test]
+    #[ignore]
+    fn bench_backends() {
+        let input = include_str!("input.txt");
+        for floored in [false, true] {
+            let start = Instant::now();
+            let hashed = fill_cave::<HashSet<Coord>>(input, floored).sand.len();
+            let hashed_time = start.elapsed();
+            let start = Instant::now();
+            let dense = fill_cave::<BitGrid>(input, floored).sand.len();
+            let dense_time = start.elapsed();
+            let start = Instant::now();
+            let mut cave = Cave::<HashSet<Coord>>::new(input);
+            cave.floored = floored;
+            let source = Coord::from((Cave::<BitGrid>::SAND_SOURCE_X, 0));
+            while !cave.drop_sand() && !cave.sand.contains(&source) {}
+            let naive_time = start.elapsed();
+            assert_eq!(hashed, dense);
+            assert_eq!(hashed, cave.sand.len());
+            println!(
+                "floored: {floored}, HashSet: {hashed_time:?}, BitGrid: {dense_time:?}, speedup: {:.1}x",
+                hashed_time.as_secs_f64() / dense_time.as_secs_f64()
+            );
+            println!("floored: {floored}, restarting from the source: {naive_time:?}");
+        }
+    }
+
     #[test]
     fn test_part_1() {
         // Adjust part 1 test to match provided challenge example
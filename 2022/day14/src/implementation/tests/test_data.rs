@@ -52,4 +52,54 @@ pub const TEST_OUTPUT_DROP_FIVE_SAND: &str = "\
 ......o.#.
 ....oooo#.
 #########.\
-";
\ No newline at end of file
+";
+pub const TEST_OUTPUT_WATER: &str = "\
+.......+...
+.......|...
+.......|...
+.......|...
+.....#.|.##
+.....#.|.#.
+...###.|.#.
+.......|.#.
+|||||||||#.
+|#########.\
+";
+
+pub const TEST_INPUT_RESERVOIR: &str = "\
+495,2 -> 495,7
+495,7 -> 501,7
+501,3 -> 501,7
+498,2 -> 498,4
+506,1 -> 506,2
+498,10 -> 498,13
+504,10 -> 504,13
+498,13 -> 504,13";
+
+pub const TEST_INPUT_OUTER_BASIN: &str = "495,2 -> 495,5 -> 505,5 -> 505,2";
+
+pub const TEST_OUTPUT_OUTER_BASIN: &str = "\
+......+......
+|||||||||||||
+|#~~~~~~~~~#|
+|#~~~~~~~~~#|
+|#~~~~~~~~~#|
+|###########|\
+";
+
+pub const TEST_OUTPUT_RESERVOIR: &str = "\
+.....+......
+.....|.....#
+#..#||||...#
+#..#~~#|....
+#..#~~#|....
+#~~~~~#|....
+#~~~~~#|....
+#######|....
+.......|....
+..|||||||||.
+..|#~~~~~#|.
+..|#~~~~~#|.
+..|#~~~~~#|.
+..|#######|.\
+";
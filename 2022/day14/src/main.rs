@@ -3,6 +3,13 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.iter().any(|x| x == "--water") {
+        let (drawing, reached, settled) = water(input);
+        println!("{drawing}");
+        println!("Water reaches {reached} cells, {settled} of them settled");
+    } else {
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+    }
 }
@@ -3,6 +3,22 @@ use std::fs;
 use strategy::*;
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    println!("Total score : {}", compute_score(&input));
-    println!("Real total score : {}", compute_real_score(&input));
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|x| x == "--rules") {
+        // --rules classic|real|rpsls|<rules file>
+        let definition = match args.get(i + 1).map(|x| x.as_str()) {
+            Some("classic") => CLASSIC.to_string(),
+            Some("real") => REAL.to_string(),
+            Some("rpsls") => RPSLS.to_string(),
+            Some(path) => fs::read_to_string(path).unwrap(),
+            None => panic!("Missing value for --rules"),
+        };
+        match Rules::new(&definition).and_then(|rules| rules.score(&input)) {
+            Ok(score) => println!("Total score : {score}"),
+            Err(e) => println!("{e}"),
+        }
+    } else {
+        println!("Total score : {}", compute_score(&input));
+        println!("Real total score : {}", compute_real_score(&input));
+    }
 }
@@ -1,84 +1,194 @@
-#[derive(PartialEq, Copy, Clone)]
-enum Play {
-    Rock,
-    Paper,
-    Scissor,
+use std::collections::{HashMap, HashSet};
+
+// Rules are written one section per line, each entry being separated by spaces :
+// - shapes: name=score for every shape
+// - beats: winner>loser pairs
+// - outcomes: win=score draw=score loss=score
+// - opponent: letter=shape
+// - player: letter=shape, or letter=outcome to play the first shape giving that outcome
+pub const CLASSIC: &str = "shapes: Rock=1 Paper=2 Scissors=3
+beats: Rock>Scissors Paper>Rock Scissors>Paper
+outcomes: win=6 draw=3 loss=0
+opponent: A=Rock B=Paper C=Scissors
+player: X=Rock Y=Paper Z=Scissors";
+
+pub const REAL: &str = "shapes: Rock=1 Paper=2 Scissors=3
+beats: Rock>Scissors Paper>Rock Scissors>Paper
+outcomes: win=6 draw=3 loss=0
+opponent: A=Rock B=Paper C=Scissors
+player: X=loss Y=draw Z=win";
+
+pub const RPSLS: &str = "shapes: Rock=1 Paper=2 Scissors=3 Lizard=4 Spock=5
+beats: Scissors>Paper Paper>Rock Rock>Lizard Lizard>Spock Spock>Scissors Scissors>Lizard \
+Lizard>Paper Paper>Spock Spock>Rock Rock>Scissors
+outcomes: win=6 draw=3 loss=0
+opponent: A=Rock B=Paper C=Scissors D=Lizard E=Spock
+player: V=Rock W=Paper X=Scissors Y=Lizard Z=Spock";
+
+fn split_entry(entry: &str, separator: char) -> Result<(&str, &str), String> {
+    entry
+        .split_once(separator)
+        .ok_or(format!("Malformed entry '{entry}'"))
 }
 
-fn parse_round(round: &str) -> (Play, Play) {
-    let mut raw = round.split_ascii_whitespace();
-    let his = match raw.next() {
-        Some("A") => Play::Rock,
-        Some("B") => Play::Paper,
-        Some("C") => Play::Scissor,
-        x => panic!("Invalid play {:?}", x),
-    };
-    let my = match raw.next() {
-        Some("X") => Play::Rock,
-        Some("Y") => Play::Paper,
-        Some("Z") => Play::Scissor,
-        x => panic!("Invalid play {:?}", x),
-    };
-    (his, my)
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Outcome {
+    Loss,
+    Draw,
+    Win,
 }
 
-fn parse_real_round(round: &str) -> (Play, Play) {
-    let mut raw = round.split_ascii_whitespace();
-    let his = match raw.next() {
-        Some("A") => Play::Rock,
-        Some("B") => Play::Paper,
-        Some("C") => Play::Scissor,
-        x => panic!("Invalid play {:?}", x),
-    };
-    let my = match raw.next() {
-        Some("X") => match his {
-            Play::Rock => Play::Scissor,
-            Play::Paper => Play::Rock,
-            Play::Scissor => Play::Paper,
-        }
-        Some("Y") => his,
-        Some("Z") => match his {
-            Play::Rock => Play::Paper,
-            Play::Paper => Play::Scissor,
-            Play::Scissor => Play::Rock,
-        },
-        x => panic!("Invalid play {:?}", x),
-    };
-    (his, my)
+#[derive(Debug, PartialEq, Copy, Clone)]
+enum Choice {
+    Shape(usize),
+    Outcome(Outcome),
 }
 
-fn play_score(play: Play) -> usize {
-    match play {
-        Play::Rock => 1,
-        Play::Paper => 2,
-        Play::Scissor => 3,
-    }
+#[derive(Debug)]
+pub struct Rules {
+    // Shape names and scores, shapes being referred to by their index
+    shapes: Vec<(String, usize)>,
+    // (winner, loser) pairs, shapes in no pair together end in a draw
+    beats: HashSet<(usize, usize)>,
+    // Indexed by Outcome
+    outcome_scores: [usize; 3],
+    opponent: HashMap<String, usize>,
+    player: HashMap<String, Choice>,
 }
 
-fn round_score(his: Play, my: Play) -> usize {
-    match (his, my) {
-        (Play::Rock, Play::Paper) | (Play::Scissor, Play::Rock) | (Play::Paper, Play::Scissor) => 6,
-        (x, y) if x == y => 3,
-        _ => 0,
+impl Rules {
+    pub fn new(definition: &str) -> Result<Self, String> {
+        let mut sections = HashMap::new();
+        for line in definition.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (name, entries) = line
+                .split_once(':')
+                .ok_or(format!("Malformed rule line '{line}'"))?;
+            let entries = entries.split_whitespace().collect::<Vec<&str>>();
+            if sections.insert(name.trim(), entries).is_some() {
+                return Err(format!("Duplicated section {}", name.trim()));
+            }
+        }
+        let mut section = |name: &str| {
+            sections
+                .remove(name)
+                .ok_or(format!("Missing section {name}"))
+        };
+        let number = |value: &str| {
+            value
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid score '{value}'"))
+        };
+
+        let mut rules = Rules {
+            shapes: vec![],
+            beats: HashSet::new(),
+            outcome_scores: [0; 3],
+            opponent: HashMap::new(),
+            player: HashMap::new(),
+        };
+        for entry in section("shapes")? {
+            let (name, score) = split_entry(entry, '=')?;
+            if rules.shape(name).is_ok() {
+                return Err(format!("Duplicated shape {name}"));
+            }
+            rules.shapes.push((name.to_string(), number(score)?));
+        }
+        for entry in section("beats")? {
+            let (winner, loser) = split_entry(entry, '>')?;
+            let (winner, loser) = (rules.shape(winner)?, rules.shape(loser)?);
+            if winner == loser || rules.beats.contains(&(loser, winner)) {
+                return Err(format!("Contradictory rule '{entry}'"));
+            }
+            rules.beats.insert((winner, loser));
+        }
+        let outcomes = section("outcomes")?;
+        for (outcome, name) in [
+            (Outcome::Loss, "loss"),
+            (Outcome::Draw, "draw"),
+            (Outcome::Win, "win"),
+        ] {
+            let score = outcomes
+                .iter()
+                .filter_map(|entry| entry.strip_prefix(name)?.strip_prefix('='))
+                .next()
+                .ok_or(format!("Missing {name} score"))?;
+            rules.outcome_scores[outcome as usize] = number(score)?;
+        }
+        for entry in section("opponent")? {
+            let (letter, shape) = split_entry(entry, '=')?;
+            rules
+                .opponent
+                .insert(letter.to_string(), rules.shape(shape)?);
+        }
+        for entry in section("player")? {
+            let (letter, choice) = split_entry(entry, '=')?;
+            let choice = match choice {
+                "loss" => Choice::Outcome(Outcome::Loss),
+                "draw" => Choice::Outcome(Outcome::Draw),
+                "win" => Choice::Outcome(Outcome::Win),
+                shape => Choice::Shape(rules.shape(shape)?),
+            };
+            rules.player.insert(letter.to_string(), choice);
+        }
+        if let Some(name) = sections.keys().next() {
+            return Err(format!("Unknown section {name}"));
+        }
+        Ok(rules)
+    }
+
+    fn shape(&self, name: &str) -> Result<usize, String> {
+        self.shapes
+            .iter()
+            .position(|(x, _)| x == name)
+            .ok_or(format!("Unknown shape {name}"))
+    }
+
+    fn outcome(&self, his: usize, my: usize) -> Outcome {
+        if self.beats.contains(&(my, his)) {
+            Outcome::Win
+        } else if self.beats.contains(&(his, my)) {
+            Outcome::Loss
+        } else {
+            Outcome::Draw
+        }
+    }
+
+    fn round_score(&self, round: &str) -> Result<usize, String> {
+        let letters = round.split_ascii_whitespace().collect::<Vec<&str>>();
+        let [his, my] = letters[..] else {
+            return Err(format!("Invalid round '{round}'"));
+        };
+        let his = *self
+            .opponent
+            .get(his)
+            .ok_or(format!("Invalid play {his}"))?;
+        let my = match self.player.get(my) {
+            Some(Choice::Shape(shape)) => *shape,
+            Some(Choice::Outcome(outcome)) => (0..self.shapes.len())
+                .find(|&x| self.outcome(his, x) == *outcome)
+                .ok_or(format!(
+                    "No shape gives a {outcome:?} against {}",
+                    self.shapes[his].0
+                ))?,
+            None => return Err(format!("Invalid play {my}")),
+        };
+        Ok(self.shapes[my].1 + self.outcome_scores[self.outcome(his, my) as usize])
+    }
+
+    pub fn score(&self, input: &str) -> Result<usize, String> {
+        input.lines().map(|x| self.round_score(x)).sum()
     }
 }
 
 pub fn compute_score(input: &str) -> usize {
-    let mut score = 0;
-    for i in input.lines() {
-        let (his, my) = parse_round(i);
-        score += play_score(my) + round_score(his, my);
-    }
-    score
+    Rules::new(CLASSIC).unwrap().score(input).unwrap()
 }
 
 pub fn compute_real_score(input: &str) -> usize {
-    let mut score = 0;
-    for i in input.lines() {
-        let (his, my) = parse_real_round(i);
-        score += play_score(my) + round_score(his, my);
-    }
-    score
+    Rules::new(REAL).unwrap().score(input).unwrap()
 }
 
 #[cfg(test)]
@@ -99,4 +209,60 @@ C Z";
         let score = compute_real_score(INPUT);
         assert_eq!(score, 12)
     }
+
+    #[test]
+    fn test_rpsls() {
+        let rules = Rules::new(RPSLS).unwrap();
+        assert_eq!(rules.beats.len(), 10);
+        // Spock vaporizes Rock, Lizard eats Paper, Scissors tie
+        assert_eq!(rules.score("A Z\nB Y\nC X"), Ok(5 + 6 + 4 + 6 + 3 + 3));
+        // Lizard poisons Spock
+        assert_eq!(rules.score("D Z"), Ok(5));
+    }
+
+    #[test]
+    fn test_custom_tournament() {
+        let rules = Rules::new(
+            "shapes: Stone=2 Water=5 Fire=1
+beats: Water>Fire Fire>Stone
+outcomes: win=10 draw=1 loss=0
+opponent: s=Stone w=Water f=Fire
+player: S=Stone L=loss W=win D=draw",
+        )
+        .unwrap();
+        // Water and Stone never beat each other
+        assert_eq!(rules.score("w S"), Ok(2 + 1));
+        // The first listed shape giving the outcome is played
+        assert_eq!(rules.score("w D"), Ok(2 + 1));
+        assert_eq!(rules.score("s W"), Ok(1 + 10));
+        assert_eq!(
+            rules.score("w W"),
+            Err("No shape gives a Win against Water".to_string())
+        );
+        assert_eq!(rules.score("s"), Err("Invalid round 's'".to_string()));
+        assert_eq!(rules.score("x S"), Err("Invalid play x".to_string()));
+    }
+
+    #[test]
+    fn test_rules_errors() {
+        for (definition, error) in [
+            ("shapes: Rock=1", "Missing section beats"),
+            (
+                &CLASSIC.replace("Rock>Scissors", "Rock>Lava"),
+                "Unknown shape Lava",
+            ),
+            (
+                &CLASSIC.replace("Scissors>Paper", "Scissors>Rock"),
+                "Contradictory rule 'Scissors>Rock'",
+            ),
+            (&CLASSIC.replace("draw=3 ", ""), "Missing draw score"),
+            (
+                &CLASSIC.replace("Paper=2", "Paper=two"),
+                "Invalid score 'two'",
+            ),
+            (&format!("{CLASSIC}\nbonus: 3"), "Unknown section bonus"),
+        ] {
+            assert_eq!(Rules::new(definition).err(), Some(error.to_string()));
+        }
+    }
 }
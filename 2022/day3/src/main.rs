@@ -1,6 +1,6 @@
 use std::fs;
 
-use rucksacks::compute_priorities_sum;
+use rucksacks::{compartments_report, compute_priorities_sum, groups_report};
 
 use crate::rucksacks::compute_badges_sum;
 
@@ -8,6 +8,26 @@ mod rucksacks;
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
-    println!("Total priority : {}", compute_priorities_sum(&input));
-    println!("Total badges priority : {}", compute_badges_sum(&input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let option = |name: &str| {
+        args.iter().position(|x| x == name).map(|i| {
+            args.get(i + 1)
+                .and_then(|x| x.parse::<usize>().ok())
+                .unwrap_or_else(|| panic!("Invalid value for {name}"))
+        })
+    };
+    let (compartments, group) = (option("--compartments"), option("--group"));
+    if compartments.is_none() && group.is_none() {
+        println!("Total priority : {}", compute_priorities_sum(&input));
+        println!("Total badges priority : {}", compute_badges_sum(&input));
+        return;
+    }
+    // Reports also list the lines not having exactly one common item
+    let priorities = compartments_report(&input, compartments.unwrap_or(2));
+    println!("Total priority : {}", priorities.sum);
+    let badges = groups_report(&input, group.unwrap_or(3));
+    println!("Total badges priority : {}", badges.sum);
+    for issue in priorities.issues.iter().chain(&badges.issues) {
+        println!("{issue}");
+    }
 }
@@ -1,43 +1,141 @@
-fn priority(c: char) -> usize {
+fn priority(c: char) -> Option<usize> {
     match c {
-        'a'..='z' => c as usize - 96,
-        'A'..='Z' => c as usize - 38,
-        _ => panic!("Unexpected char {c}"),
+        'a'..='z' => Some(c as usize - 96),
+        'A'..='Z' => Some(c as usize - 38),
+        _ => None,
     }
 }
 
-pub fn compute_priorities_sum(input: &str) -> usize {
-    let mut priorities = 0;
-    for line in input.lines() {
-        let duplicate = line[..line.len() / 2]
-            .chars()
-            .find(|x| line[line.len() / 2..].contains(*x))
-            .expect("No duplicate found");
-        let priority = priority(duplicate);
-        priorities += priority;
+// One bit per item type, bit i standing for priority i + 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ItemSet(u64);
+
+impl ItemSet {
+    const ALL: ItemSet = ItemSet((1 << 52) - 1);
+
+    fn new(items: &str) -> Result<Self, char> {
+        items.chars().try_fold(ItemSet(0), |set, c| {
+            priority(c).map(|p| ItemSet(set.0 | 1 << (p - 1))).ok_or(c)
+        })
+    }
+
+    fn intersection(self, other: ItemSet) -> ItemSet {
+        ItemSet(self.0 & other.0)
+    }
+
+    fn len(self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    fn priorities_sum(self) -> usize {
+        (0..52)
+            .filter(|i| self.0 & 1 << i != 0)
+            .map(|i| i + 1)
+            .sum()
+    }
+
+    fn items(self) -> String {
+        ('a'..='z')
+            .chain('A'..='Z')
+            .enumerate()
+            .filter(|(i, _)| self.0 & 1 << i != 0)
+            .map(|(_, c)| c)
+            .collect()
     }
-    priorities
 }
 
-pub fn compute_badges_sum(input: &str) -> usize {
-    let mut iter = input.lines();
-    let mut priorities = 0;
-    loop {
-        match iter.next() {
-            None => break,
-            x => {
-                let second_sack = iter.next();
-                let third_sack = iter.next();
-                let badge = x
-                    .expect("No line available")
-                    .chars()
-                    .find(|x| second_sack.unwrap().contains(*x) && third_sack.unwrap().contains(*x))
-                    .unwrap();
-                priorities += priority(badge);
+// Sum of the priorities of the common items, and the lines not having exactly one of them
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub sum: usize,
+    pub issues: Vec<String>,
+}
+
+impl Report {
+    fn new() -> Self {
+        Report {
+            sum: 0,
+            issues: vec![],
+        }
+    }
+
+    fn add(&mut self, lines: &str, parts: &[&str]) {
+        let mut common = ItemSet::ALL;
+        for part in parts {
+            match ItemSet::new(part) {
+                Ok(set) => common = common.intersection(set),
+                Err(c) => {
+                    self.issues.push(format!("{lines} : unexpected item '{c}'"));
+                    return;
+                }
             }
         }
+        match common.len() {
+            0 => self.issues.push(format!("{lines} : no common item")),
+            1 => (),
+            _ => self
+                .issues
+                .push(format!("{lines} : several common items {}", common.items())),
+        }
+        self.sum += common.priorities_sum();
     }
-    priorities
+}
+
+// Each line is split in `compartments` parts of the same size
+pub fn compartments_report(input: &str, compartments: usize) -> Report {
+    assert!(compartments > 0, "At least one compartment is needed");
+    let mut report = Report::new();
+    for (index, line) in input.lines().enumerate() {
+        let items = line.chars().collect::<Vec<char>>();
+        if items.is_empty() {
+            report
+                .issues
+                .push(format!("Line {} : empty rucksack", index + 1));
+            continue;
+        }
+        if items.len() % compartments != 0 {
+            report.issues.push(format!(
+                "Line {} : {} items can't be split in {compartments} compartments",
+                index + 1,
+                items.len()
+            ));
+            continue;
+        }
+        let parts = items
+            .chunks((items.len() / compartments).max(1))
+            .map(|x| x.iter().collect::<String>())
+            .collect::<Vec<String>>();
+        let parts = parts.iter().map(|x| x.as_str()).collect::<Vec<&str>>();
+        report.add(&format!("Line {}", index + 1), &parts);
+    }
+    report
+}
+
+// Consecutive lines are grouped by `group_size`
+pub fn groups_report(input: &str, group_size: usize) -> Report {
+    assert!(group_size > 0, "Groups need at least one elf");
+    let mut report = Report::new();
+    let lines = input.lines().collect::<Vec<&str>>();
+    for (index, group) in lines.chunks(group_size).enumerate() {
+        let first = index * group_size + 1;
+        let name = format!("Lines {first}-{}", first + group.len() - 1);
+        if group.len() < group_size {
+            report
+                .issues
+                .push(format!("{name} : incomplete group of {}", group.len()));
+            continue;
+        }
+        report.add(&name, group);
+    }
+    report
+}
+
+pub fn compute_priorities_sum(input: &str) -> usize {
+    compartments_report(input, 2).sum
+}
+
+pub fn compute_badges_sum(input: &str) -> usize {
+    groups_report(input, 3).sum
 }
 
 #[cfg(test)]
@@ -60,4 +158,43 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
     fn compute_badges_priority() {
         assert_eq!(compute_badges_sum(INPUT), 70)
     }
+
+    #[test]
+    fn item_sets() {
+        let set = ItemSet::new("abzAZa").unwrap();
+        assert_eq!(set.len(), 5);
+        assert_eq!(set.items(), "abzAZ");
+        assert_eq!(set.priorities_sum(), 1 + 2 + 26 + 27 + 52);
+        assert_eq!(ItemSet::new("ab1"), Err('1'));
+        assert_eq!(ItemSet::ALL.intersection(set), set);
+        assert_eq!(priority('-'), None);
+    }
+
+    #[test]
+    fn report_issues() {
+        let report = compartments_report("abcabc\nabcd\nab-b\nabc", 2);
+        assert_eq!(report.sum, 1 + 2 + 3);
+        assert_eq!(
+            report.issues,
+            vec![
+                "Line 1 : several common items abc",
+                "Line 2 : no common item",
+                "Line 3 : unexpected item '-'",
+                "Line 4 : 3 items can't be split in 2 compartments",
+            ]
+        );
+        assert_eq!(compartments_report("aXbcXdeXf", 3).sum, 50);
+        let report = compartments_report(&INPUT.replacen('\n', "\n\n", 1), 2);
+        assert_eq!(report.sum, 157);
+        assert_eq!(report.issues, vec!["Line 2 : empty rucksack"]);
+        let report = groups_report(INPUT, 4);
+        assert_eq!(
+            report.issues,
+            vec![
+                "Lines 1-4 : no common item",
+                "Lines 5-6 : incomplete group of 2"
+            ]
+        );
+        assert_eq!(groups_report(INPUT, 3).issues.len(), 0);
+    }
 }
@@ -1,55 +1,266 @@
-use std::fs;
+use std::{fmt, fs, str::FromStr};
 
 fn main() {
     let input = fs::read_to_string("input.txt").unwrap();
     println!("Containing pairs : {}", count_containing_pairs(&input));
     println!("Overlapping pairs : {}", count_overlaps(&input));
+    println!("Shared sections : {}", count_shared_sections(&input));
+    println!("{}", report(&input));
 }
 
-fn get_numeric_ids(line: &str) -> Vec<Vec<u8>> {
-    line
-    .split(',')
-    .map(|x| {
-        x.split('-')
-            .map(|y| y.parse::<u8>().unwrap())
-            .collect::<Vec<u8>>()
-    })
-    .collect()
+// Inclusive range of section IDs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Interval {
+    start: u64,
+    end: u64,
 }
 
-fn contains(raw_ids: Vec<Vec<u8>>) -> bool {
-    raw_ids[0][0] >= raw_ids[1][0] && raw_ids[0][1] <= raw_ids[1][1]
-    || raw_ids[0][0] <= raw_ids[1][0] && raw_ids[0][1] >= raw_ids[1][1]
+impl Interval {
+    fn new(start: u64, end: u64) -> Result<Self, String> {
+        if start > end {
+            return Err(format!("Empty range {start}-{end}"));
+        }
+        // Lengths and sweep bounds go one past the end of a range
+        if end == u64::MAX {
+            return Err(format!("Section ID {end} is too big"));
+        }
+        Ok(Interval { start, end })
+    }
+
+    fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    fn contains(&self, other: &Interval) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    fn intersection(&self, other: &Interval) -> Option<Interval> {
+        Interval::new(self.start.max(other.start), self.end.min(other.end)).ok()
+    }
 }
 
-fn overlaps(raw_ids: Vec<Vec<u8>>) -> bool {
-    !(raw_ids[0][0] > raw_ids[1][1] || raw_ids[0][1] < raw_ids[1][0])
+impl FromStr for Interval {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .trim()
+            .split_once('-')
+            .ok_or(format!("Malformed range '{s}'"))?;
+        let parse = |x: &str| {
+            x.parse::<u64>()
+                .map_err(|_| format!("Malformed section ID '{x}'"))
+        };
+        Interval::new(parse(start)?, parse(end)?)
+    }
 }
 
-pub fn count_containing_pairs(input: &str) -> usize {
-    let mut sum = 0;
-    for line in input.lines() {
-        let raw_ids: Vec<Vec<u8>> = get_numeric_ids(line);
-        match contains(raw_ids) 
-        {
-            true => sum += 1,
-            _ => continue
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.end)
+    }
+}
+
+// Sorted intervals, merged as soon as they overlap or touch
+#[derive(Debug, Clone, Default, PartialEq)]
+struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    fn new(intervals: impl IntoIterator<Item = Interval>) -> Self {
+        let mut result = IntervalSet::default();
+        let mut intervals = intervals.into_iter().collect::<Vec<Interval>>();
+        intervals.sort();
+        for interval in intervals {
+            result.merge(interval);
+        }
+        result
+    }
+
+    // Appends an interval starting after every interval of the set
+    fn merge(&mut self, interval: Interval) {
+        match self.intervals.last_mut() {
+            Some(last) if interval.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(interval.end)
+            }
+            _ => self.intervals.push(interval),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    fn coverage(&self) -> u64 {
+        self.intervals.iter().map(|x| x.len()).sum()
+    }
+
+    fn union(&self, other: &IntervalSet) -> IntervalSet {
+        IntervalSet::new(self.intervals.iter().chain(&other.intervals).copied())
+    }
+
+    fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = IntervalSet::default();
+        let (mut i, mut j) = (0, 0);
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            if let Some(common) = a.intersection(&b) {
+                result.merge(common);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        result
+    }
+
+    fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = IntervalSet::default();
+        let mut removed = other.intervals.iter().peekable();
+        for interval in self.intervals.iter() {
+            let mut start = interval.start;
+            // Skip what ends before the interval, then cut out what overlaps it
+            while removed.next_if(|r| r.end < start).is_some() {}
+            while let Some(r) = removed.peek().filter(|r| r.start <= interval.end) {
+                if r.start > start {
+                    result.merge(Interval::new(start, r.start - 1).unwrap());
+                }
+                if r.end >= interval.end {
+                    break;
+                }
+                start = r.end + 1;
+                removed.next();
+            }
+            if removed.peek().is_none_or(|r| r.start > interval.end) {
+                result.merge(Interval::new(start, interval.end).unwrap());
+            }
         }
+        result
     }
-    sum
+}
+
+impl fmt::Display for IntervalSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let intervals = self
+            .intervals
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<String>>();
+        write!(f, "{}", intervals.join(","))
+    }
+}
+
+// Any number of comma separated ranges
+fn parse_line(line: &str) -> Result<Vec<Interval>, String> {
+    line.split(',').map(|x| x.parse::<Interval>()).collect()
+}
+
+fn parse_input(input: &str) -> Vec<Vec<Interval>> {
+    input
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| parse_line(x).unwrap())
+        .collect()
+}
+
+// One range of the line fully contains another one
+fn contains(ranges: &[Interval]) -> bool {
+    ranges.iter().enumerate().any(|(i, a)| {
+        ranges
+            .iter()
+            .enumerate()
+            .any(|(j, b)| i != j && a.contains(b))
+    })
+}
+
+// Ranges overlap when merging them loses some sections
+fn overlaps(ranges: &[Interval]) -> bool {
+    let total: u64 = ranges.iter().map(|x| x.len()).sum();
+    IntervalSet::new(ranges.iter().copied()).coverage() < total
+}
+
+pub fn count_containing_pairs(input: &str) -> usize {
+    parse_input(input).iter().filter(|x| contains(x)).count()
 }
 
 pub fn count_overlaps(input: &str) -> usize {
-    let mut sum = 0;
-    for line in input.lines() {
-        let raw_ids: Vec<Vec<u8>> = get_numeric_ids(line);
-        match overlaps(raw_ids) 
-        {
-            true => sum += 1,
-            _ => continue
+    parse_input(input).iter().filter(|x| overlaps(x)).count()
+}
+
+// Sections assigned to every elf of a line, summed over all lines
+pub fn count_shared_sections(input: &str) -> u64 {
+    parse_input(input)
+        .iter()
+        .map(|ranges| {
+            ranges
+                .iter()
+                .map(|x| IntervalSet::new([*x]))
+                .reduce(|acc, x| acc.intersection(&x))
+                .map_or(0, |x| x.coverage())
+        })
+        .sum()
+}
+
+// Sections between the lowest and the highest assigned ones that nobody has to clean
+fn uncovered_sections(input: &str) -> IntervalSet {
+    let assigned = parse_input(input)
+        .iter()
+        .map(|x| IntervalSet::new(x.iter().copied()))
+        .fold(IntervalSet::default(), |acc, x| acc.union(&x));
+    if assigned.is_empty() {
+        return assigned;
+    }
+    let (first, last) = (
+        assigned.intervals[0],
+        assigned.intervals[assigned.intervals.len() - 1],
+    );
+    IntervalSet::new([Interval::new(first.start, last.end).unwrap()]).difference(&assigned)
+}
+
+// Highest number of ranges covering a same section, and the sections where it happens
+fn max_concurrent_coverage(input: &str) -> (usize, IntervalSet) {
+    // Sweep over range bounds, a range stops covering right after its end
+    let mut events = parse_input(input)
+        .into_iter()
+        .flatten()
+        .flat_map(|x| [(x.start, 1), (x.end + 1, -1)])
+        .collect::<Vec<(u64, i64)>>();
+    events.sort();
+    let (mut current, mut max) = (0, 0);
+    let mut sections = vec![];
+    for (i, &(position, delta)) in events.iter().enumerate() {
+        current += delta;
+        // Only consider the count once every event of this position has been applied
+        if events.get(i + 1).is_some_and(|next| next.0 == position) {
+            continue;
+        }
+        if current > max {
+            max = current;
+            sections.clear();
+        }
+        if current == max && max > 0 {
+            let next = events[i + 1].0;
+            sections.push(Interval::new(position, next - 1).unwrap());
         }
     }
-    sum
+    (max as usize, IntervalSet::new(sections))
+}
+
+pub fn report(input: &str) -> String {
+    let (max, sections) = max_concurrent_coverage(input);
+    let uncovered = uncovered_sections(input);
+    format!(
+        "Sections covered by nobody : {}\nMax concurrent coverage : {max} on {sections}",
+        if uncovered.is_empty() {
+            "none".to_string()
+        } else {
+            uncovered.to_string()
+        }
+    )
 }
 
 #[cfg(test)]
@@ -69,4 +280,49 @@ mod test {
     fn test_count_overlaps() {
         assert_eq!(count_overlaps(INPUT), 4);
     }
+    #[test]
+    fn test_large_ids_and_many_ranges() {
+        assert_eq!(count_containing_pairs("1000-2000,300-400,1500-1600"), 1);
+        assert_eq!(count_overlaps("1000-2000,300-400,2100-2200"), 0);
+        assert_eq!(count_overlaps("1000-2000,300-400,2000-2200"), 1);
+        assert_eq!(count_shared_sections("1-10,5-20,8-30\n1-2,3-4"), 3);
+        assert!(parse_line("1-2,3").is_err());
+        assert!(parse_line("4-3").is_err());
+        assert!(parse_line("1-x").is_err());
+        assert_eq!(
+            parse_line(&format!("0-{}", u64::MAX)),
+            Err(format!("Section ID {} is too big", u64::MAX))
+        );
+        let max = u64::MAX - 1;
+        assert_eq!(count_shared_sections(&format!("0-{max},{max}-{max}")), 1);
+        assert_eq!(max_concurrent_coverage(&format!("0-{max}")).0, 1);
+    }
+    #[test]
+    fn test_interval_set() {
+        let set = |x: &str| IntervalSet::new(parse_line(x).unwrap());
+        let a = set("1-5,10-20,3-7,21-22");
+        assert_eq!(a.to_string(), "1-7,10-22");
+        assert_eq!(a.coverage(), 20);
+        let b = set("6-12,15-15,30-40");
+        assert_eq!(a.union(&b).to_string(), "1-22,30-40");
+        assert_eq!(a.intersection(&b).to_string(), "6-7,10-12,15-15");
+        assert_eq!(a.difference(&b).to_string(), "1-5,13-14,16-22");
+        assert_eq!(b.difference(&a).to_string(), "8-9,30-40");
+        assert!(a.difference(&a).is_empty());
+        assert_eq!(a.difference(&IntervalSet::default()), a);
+        assert!(IntervalSet::default().intersection(&a).is_empty());
+    }
+    #[test]
+    fn test_uncovered_and_concurrent_coverage() {
+        assert_eq!(uncovered_sections(INPUT).to_string(), "");
+        assert_eq!(
+            uncovered_sections("2-4,8-9\n12-12").to_string(),
+            "5-7,10-11"
+        );
+        let (max, sections) = max_concurrent_coverage(INPUT);
+        assert_eq!((max, sections.to_string()), (8, "6-6".to_string()));
+        let (max, sections) = max_concurrent_coverage("1-3,3-5\n5-5,7-9");
+        assert_eq!((max, sections.to_string()), (2, "3-3,5-5".to_string()));
+        assert_eq!(max_concurrent_coverage("").0, 0);
+    }
 }
@@ -3,88 +3,313 @@ struct Instruction {
     src: usize,
     dst: usize,
 }
+
+// Crate labels may be longer than a single character
+type Crate = String;
+
+// A crane moves the crates of one instruction, stacks having their top at the end
+trait Crane {
+    fn move_crates(&self, stacks: &mut [Vec<Crate>], instruction: &Instruction);
+}
+
+// CrateMover 9000 : one crate at a time
+struct OneAtATime;
+// CrateMover 9001 : all the crates at once
+struct WholeStack;
+// Moves up to `capacity` crates at once, in as many chunks as needed
+struct Capacity(usize);
+
+impl Crane for OneAtATime {
+    fn move_crates(&self, stacks: &mut [Vec<Crate>], instruction: &Instruction) {
+        for _ in 0..instruction.count {
+            let element = stacks[instruction.src - 1].pop().unwrap();
+            stacks[instruction.dst - 1].push(element);
+        }
+    }
+}
+
+impl Crane for WholeStack {
+    fn move_crates(&self, stacks: &mut [Vec<Crate>], instruction: &Instruction) {
+        Capacity(instruction.count).move_crates(stacks, instruction);
+    }
+}
+
+impl Crane for Capacity {
+    fn move_crates(&self, stacks: &mut [Vec<Crate>], instruction: &Instruction) {
+        let mut remaining = instruction.count;
+        while remaining > 0 {
+            let chunk = remaining.min(self.0);
+            let src = &mut stacks[instruction.src - 1];
+            let crates_to_move = src.split_off(src.len() - chunk);
+            stacks[instruction.dst - 1].extend(crates_to_move);
+            remaining -= chunk;
+        }
+    }
+}
+
+// Crane models by name : "one", "whole" or a maximum number of crates moved at once
+fn crane_model(name: &str) -> Result<Box<dyn Crane>, String> {
+    match name {
+        "one" => Ok(Box::new(OneAtATime)),
+        "whole" => Ok(Box::new(WholeStack)),
+        _ => match name.parse::<usize>() {
+            Ok(0) => Err("Crane capacity must be at least 1".to_string()),
+            Ok(capacity) => Ok(Box::new(Capacity(capacity))),
+            Err(_) => Err(format!("Unknown crane model {name}")),
+        },
+    }
+}
+
 struct Crates {
-    stacks: Vec<Vec<char>>,
-    instructions: Vec<Instruction>
+    stacks: Vec<Vec<Crate>>,
+    instructions: Vec<Instruction>,
+    // Crates taken from the source stack by each executed instruction, top last
+    log: Vec<Vec<Crate>>,
 }
 
 impl Instruction {
-    pub fn new(input: &str) -> Self {
+    pub fn new(input: &str) -> Result<Self, String> {
         // Input line has the form :
         // "move X from Y to Z\n"
-        let mut raw_data = input.split_ascii_whitespace().skip(1).step_by(2);
-        Instruction {
-            count: raw_data.next().unwrap().parse::<usize>().unwrap(),
-            src: raw_data.next().unwrap().parse::<usize>().unwrap(),
-            dst: raw_data.next().unwrap().parse::<usize>().unwrap(),
+        let words = input.split_ascii_whitespace().collect::<Vec<&str>>();
+        let error = || format!("Malformed instruction '{input}'");
+        if words.len() != 6 || words[0] != "move" || words[2] != "from" || words[4] != "to" {
+            return Err(error());
+        }
+        let value = |i: usize| words[i].parse::<usize>().map_err(|_| error());
+        Ok(Instruction {
+            count: value(1)?,
+            src: value(3)?,
+            dst: value(5)?,
+        })
+    }
+}
+
+// Column spans (first and last character) of each word of a line, as (start, end, word)
+fn words_with_columns(line: &str) -> Vec<(usize, usize, String)> {
+    let mut result: Vec<(usize, usize, String)> = vec![];
+    let mut previous = ' ';
+    for (column, c) in line.chars().enumerate() {
+        if !c.is_whitespace() {
+            if previous.is_whitespace() {
+                result.push((column, column, String::new()));
+            }
+            let word = result.last_mut().unwrap();
+            word.1 = column;
+            word.2.push(c);
         }
+        previous = c;
     }
+    result
 }
 
 impl Crates {
-    pub fn new(input: &str) -> Self {
-        let mut lines = input.lines();
-        let mut line = lines.next();
-        let nb_stacks = (line.unwrap().len() + 1) / 4;
+    pub fn new(input: &str) -> Result<Self, String> {
+        let lines = input.lines().collect::<Vec<&str>>();
+        // The indexes line is the first one only made of numbers
+        let index_line = lines
+            .iter()
+            .position(|l| {
+                !l.trim().is_empty() && l.split_whitespace().all(|w| w.parse::<usize>().is_ok())
+            })
+            .ok_or("Missing stack indexes line")?;
+        let indexes = words_with_columns(lines[index_line]);
+        for (i, (_, _, label)) in indexes.iter().enumerate() {
+            if label.parse::<usize>() != Ok(i + 1) {
+                return Err(format!("Stack {label} found at position {}", i + 1));
+            }
+        }
         let mut result = Crates {
-            stacks: vec![vec![]; nb_stacks],
-            instructions: vec![]
+            stacks: vec![vec![]; indexes.len()],
+            instructions: vec![],
+            log: vec![],
         };
-        'parsing_stacks: while line.is_some() {
-            for (index, char) in line.unwrap().chars().skip(1).step_by(4).enumerate() {
-                if char.is_alphabetic() {
-                    result.stacks[index].push(char);
-                } else if char.is_ascii_digit() {
-                    // We have reached the crates indexes line
-                    break 'parsing_stacks;
+
+        // Each crate belongs to the only stack whose index is below it, and must lie on the crate
+        // below it down to the bottom of the stack
+        let mut last_rows: Vec<Option<usize>> = vec![None; indexes.len()];
+        for (row, line) in lines[..index_line].iter().enumerate() {
+            for (start, end, word) in words_with_columns(line) {
+                let label = word
+                    .strip_prefix('[')
+                    .and_then(|w| w.strip_suffix(']'))
+                    .filter(|l| !l.is_empty() && !l.contains(['[', ']']))
+                    .ok_or(format!("Malformed crate '{word}' on line {}", row + 1))?;
+                let mut stacks = indexes
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (s, e, _))| *s <= end && start <= *e)
+                    .map(|(i, _)| i);
+                let stack = match (stacks.next(), stacks.next()) {
+                    (Some(stack), None) => stack,
+                    _ => {
+                        return Err(format!(
+                            "Crate {word} on line {} is not above a single stack",
+                            row + 1
+                        ))
+                    }
+                };
+                if last_rows[stack].is_some_and(|r| r + 1 != row) {
+                    return Err(format!("Crate {word} on line {} is floating", row + 1));
                 }
+                last_rows[stack] = Some(row);
+                result.stacks[stack].push(label.to_string());
             }
-            line = lines.next();
         }
-        // Skip crates indexes line
-        lines.next();
-        // Skip empty line
-        line = lines.next();
+        for (i, last_row) in last_rows.iter().enumerate() {
+            if last_row.is_some_and(|r| r + 1 != index_line) {
+                return Err(format!("Stack {} is floating above its index", i + 1));
+            }
+        }
+        // Crates were read from the top
+        for stack in result.stacks.iter_mut() {
+            stack.reverse();
+        }
 
         // Parse moving instructions
-        while line.is_some() {
-            result.instructions.push(Instruction::new(line.unwrap()));
-            line = lines.next();
+        for line in lines[index_line + 1..]
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+        {
+            let instruction = Instruction::new(line)?;
+            for stack in [instruction.src, instruction.dst] {
+                if stack == 0 || stack > result.stacks.len() {
+                    return Err(format!("Unknown stack {stack} in '{line}'"));
+                }
+            }
+            result.instructions.push(instruction);
         }
-        result
+        Ok(result)
     }
 
-    pub fn execute_instructions(&mut self) {
-        for instruction in self.instructions.iter() {
-            for _ in 0..instruction.count {
-                let element = self.stacks[instruction.src-1].remove(0);
-                self.stacks[instruction.dst-1].insert(0, element);
-            }
+    // Executes the next instruction
+    pub fn step(&mut self, crane: &dyn Crane) -> Result<(), String> {
+        let instruction = self
+            .instructions
+            .get(self.log.len())
+            .ok_or("No instruction left")?;
+        let src = &self.stacks[instruction.src - 1];
+        if src.len() < instruction.count {
+            return Err(format!(
+                "Instruction {} moves {} crates from stack {} holding {}",
+                self.log.len() + 1,
+                instruction.count,
+                instruction.src,
+                src.len()
+            ));
         }
+        let taken = src[src.len() - instruction.count..].to_vec();
+        crane.move_crates(&mut self.stacks, instruction);
+        self.log.push(taken);
+        Ok(())
+    }
+
+    // Whatever the crane, the crates moved are on top of the destination and go back as taken
+    pub fn undo(&mut self) -> Result<(), String> {
+        let taken = self.log.pop().ok_or("No instruction to undo")?;
+        let instruction = &self.instructions[self.log.len()];
+        let dst = &mut self.stacks[instruction.dst - 1];
+        dst.truncate(dst.len() - taken.len());
+        self.stacks[instruction.src - 1].extend(taken);
+        Ok(())
     }
 
-    pub fn execute_instructions_with_better_crane(&mut self) {
-        for instruction in self.instructions.iter() {
-            let mut crates_to_move = self.stacks[instruction.src-1].drain(..instruction.count).collect::<Vec<char>>();
-            crates_to_move.append(&mut self.stacks[instruction.dst-1]);
-            self.stacks[instruction.dst-1] = crates_to_move;
+    // Moves forward or backward until exactly `step` instructions have been executed
+    pub fn replay_to(&mut self, step: usize, crane: &dyn Crane) -> Result<(), String> {
+        if step > self.instructions.len() {
+            return Err(format!(
+                "Step {step} is out of the {} instructions",
+                self.instructions.len()
+            ));
         }
+        while self.log.len() > step {
+            self.undo()?;
+        }
+        while self.log.len() < step {
+            self.step(crane)?;
+        }
+        Ok(())
     }
 
+    pub fn execute_instructions(&mut self, crane: &dyn Crane) -> Result<(), String> {
+        self.replay_to(self.instructions.len(), crane)
+    }
+
+    pub fn top_elements(&self) -> Vec<Option<&Crate>> {
+        self.stacks.iter().map(|x| x.last()).collect()
+    }
+
+    // Empty stacks are shown as a space to keep the other crates in place
     pub fn get_top_elements(&self) -> String {
-        self.stacks.iter().fold(String::new(), |acc, x| {acc + &String::from(x[0])})
+        self.top_elements()
+            .into_iter()
+            .map(|x| x.map_or(" ", |c| c.as_str()))
+            .collect()
     }
+
+    // Drawing in the input format, every crate being as wide as the longest label
+    pub fn render(&self) -> String {
+        let width = self
+            .stacks
+            .iter()
+            .flatten()
+            .map(|c| c.chars().count() + 2)
+            .chain([3, self.stacks.len().to_string().len()])
+            .max()
+            .unwrap();
+        let center = |text: String| {
+            let left = (width - text.chars().count()) / 2;
+            format!(
+                "{}{text}{}",
+                " ".repeat(left),
+                " ".repeat(width - left - text.chars().count())
+            )
+        };
+        let height = self.stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+        let mut lines = (0..height)
+            .rev()
+            .map(|level| {
+                self.stacks
+                    .iter()
+                    .map(|s| match s.get(level) {
+                        Some(c) => center(format!("[{c}]")),
+                        None => " ".repeat(width),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            })
+            .collect::<Vec<String>>();
+        lines.push(
+            (1..=self.stacks.len())
+                .map(|i| center(i.to_string()))
+                .collect::<Vec<String>>()
+                .join(" "),
+        );
+        lines.join("\n")
+    }
+}
+
+pub fn top_elements_with_crane(input: &str, crane: &str) -> Result<String, String> {
+    let mut supply = Crates::new(input)?;
+    supply.execute_instructions(crane_model(crane)?.as_ref())?;
+    Ok(supply.get_top_elements())
+}
+
+pub fn render_after_step(input: &str, crane: &str, step: usize) -> Result<String, String> {
+    let mut supply = Crates::new(input)?;
+    supply.replay_to(step, crane_model(crane)?.as_ref())?;
+    Ok(supply.render())
 }
 
 pub fn part_1(input: &str) -> String {
-    let mut supply = Crates::new(input);
-    supply.execute_instructions();
+    let mut supply = Crates::new(input).unwrap();
+    supply.execute_instructions(&OneAtATime).unwrap();
     supply.get_top_elements()
 }
 
 pub fn part_2(input: &str) -> String {
-    let mut supply = Crates::new(input);
-    supply.execute_instructions_with_better_crane();
+    let mut supply = Crates::new(input).unwrap();
+    supply.execute_instructions(&WholeStack).unwrap();
     supply.get_top_elements()
 }
 
@@ -108,6 +333,143 @@ move 1 from 1 to 2";
         assert_eq!(part_1(TEST_INPUT), "CMZ")
     }
 
+    #[test]
+    fn test_crane_models() {
+        for (crane, top) in [
+            ("one", "CMZ"),
+            ("whole", "MCD"),
+            ("1", "CMZ"),
+            ("2", "MCZ"),
+            ("3", "MCD"),
+        ] {
+            assert_eq!(
+                top_elements_with_crane(TEST_INPUT, crane),
+                Ok(top.to_string())
+            );
+        }
+        assert!(top_elements_with_crane(TEST_INPUT, "0").is_err());
+        assert!(top_elements_with_crane(TEST_INPUT, "9002").is_ok());
+        assert!(top_elements_with_crane(TEST_INPUT, "magnet").is_err());
+    }
+
+    #[test]
+    fn test_parse_trimmed_lines() {
+        let trimmed = TEST_INPUT
+            .lines()
+            .map(|l| l.trim_end())
+            .collect::<Vec<&str>>()
+            .join("\n");
+        assert_eq!(part_1(&trimmed), "CMZ");
+        assert_eq!(part_2(&trimmed), "MCD");
+    }
+
+    #[test]
+    fn test_parse_many_stacks() {
+        let input = "                                            [L]
+[A] [B] [C] [D] [E] [F] [G] [H] [I] [J] [K] [M]
+ 1   2   3   4   5   6   7   8   9  10  11  12
+
+move 2 from 12 to 1
+move 1 from 9 to 10";
+        let crates = Crates::new(input).unwrap();
+        assert_eq!(crates.stacks.len(), 12);
+        assert_eq!(crates.stacks[11], vec!["M", "L"]);
+        assert_eq!(part_1(input), "MBCDEFGH IK ");
+    }
+
+    #[test]
+    fn test_parse_long_labels() {
+        let input = "[AA]
+[BB] [CC]   [D]
+ 1    2     3
+
+move 1 from 1 to 3";
+        let mut crates = Crates::new(input).unwrap();
+        assert_eq!(crates.stacks, vec![vec!["BB", "AA"], vec!["CC"], vec!["D"]]);
+        crates.execute_instructions(&OneAtATime).unwrap();
+        assert_eq!(
+            crates.top_elements(),
+            vec![
+                Some(&"BB".to_string()),
+                Some(&"CC".to_string()),
+                Some(&"AA".to_string())
+            ]
+        );
+        assert_eq!(crates.get_top_elements(), "BBCCAA");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for (input, error) in [
+            ("[A]\n\nmove 1 from 1 to 1", "Missing stack indexes line"),
+            ("[A]\n 2", "Stack 2 found at position 1"),
+            ("[A] B\n 1  2", "Malformed crate 'B' on line 1"),
+            (
+                "      [A]\n 1  2",
+                "Crate [A] on line 1 is not above a single stack",
+            ),
+            ("[A]\n\n[B]\n 1", "Crate [B] on line 3 is floating"),
+            ("[A]\n\n 1", "Stack 1 is floating above its index"),
+            (
+                "[A]\n 1\n\nmove one from 1 to 1",
+                "Malformed instruction 'move one from 1 to 1'",
+            ),
+            (
+                "[A]\n 1\n\nmove 1 from 1 to 2",
+                "Unknown stack 2 in 'move 1 from 1 to 2'",
+            ),
+        ] {
+            assert_eq!(Crates::new(input).err(), Some(error.to_string()), "{input}");
+        }
+    }
+
+    #[test]
+    fn test_replay_and_undo() {
+        for crane in [&OneAtATime as &dyn Crane, &WholeStack, &Capacity(2)] {
+            let mut crates = Crates::new(TEST_INPUT).unwrap();
+            let mut drawings = vec![crates.render()];
+            for _ in 0..crates.instructions.len() {
+                crates.step(crane).unwrap();
+                drawings.push(crates.render());
+            }
+            assert!(crates.step(crane).is_err());
+            for step in [2, 0, 4, 1, 3] {
+                crates.replay_to(step, crane).unwrap();
+                assert_eq!(crates.render(), drawings[step]);
+            }
+            while crates.undo().is_ok() {}
+            assert_eq!(crates.render(), drawings[0]);
+            assert!(crates.replay_to(5, crane).is_err());
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let drawing = TEST_INPUT.split("\n\n").next().unwrap();
+        assert_eq!(
+            render_after_step(TEST_INPUT, "one", 0),
+            Ok(drawing.to_string())
+        );
+        assert_eq!(
+            render_after_step(TEST_INPUT, "one", 2),
+            Ok("        [Z]\n        [N]\n    [C] [D]\n    [M] [P]\n 1   2   3 ".to_string())
+        );
+        let input = "[AA]\n[BB] [CC]   [D]\n 1    2     3\n\nmove 1 from 1 to 3";
+        let drawing = render_after_step(input, "one", 1).unwrap();
+        assert_eq!(drawing, "          [AA]\n[BB] [CC] [D] \n 1    2    3  ");
+        let crates = Crates::new(&drawing).unwrap();
+        assert_eq!(crates.stacks, vec![vec!["BB"], vec!["CC"], vec!["D", "AA"]]);
+    }
+
+    #[test]
+    fn test_move_too_many_crates() {
+        let input = "[A]\n 1  2\n\nmove 2 from 1 to 2";
+        assert_eq!(
+            top_elements_with_crane(input, "one"),
+            Err("Instruction 1 moves 2 crates from stack 1 holding 1".to_string())
+        );
+    }
+
     #[test]
     fn test_part_2() {
         // Adjust part 2 test to match provided challenge example
@@ -3,6 +3,28 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input)); 
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let value = |name: &str| {
+        args.iter().position(|x| x == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {name}"))
+        })
+    };
+    // --crane one|whole|N, N being the number of crates moved at once
+    let crane = value("--crane");
+    let result = if let Some(step) = value("--step") {
+        let step = step.parse::<usize>().expect("Invalid value for --step");
+        render_after_step(input, crane.map_or("one", |c| c), step)
+    } else if let Some(crane) = crane {
+        top_elements_with_crane(input, crane)
+            .map(|top| format!("Top crates with crane {crane} : {top}"))
+    } else {
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+        return;
+    };
+    match result {
+        Ok(output) => println!("{output}"),
+        Err(e) => println!("{e}"),
+    }
 }
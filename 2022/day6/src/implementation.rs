@@ -1,23 +1,140 @@
-pub fn search_pattern(input: &str, pattern_size:usize) -> usize
-{
-    let signal = input.chars().collect::<Vec<char>>();
-    for i in 0..signal.len()-pattern_size {
-        let mut part = Vec::from(&signal[i..i+pattern_size]);
-        part.sort();
-        part.dedup();
-        if part.len() == pattern_size {
-            return i+pattern_size;
+use std::{
+    io::{self, BufReader, Read},
+    ops::Range,
+};
+
+// Position right after the first window of `pattern_size` distinct bytes, reading the stream
+// only once while counting the occurrences of each byte in the current window
+pub fn find_marker<R: Read>(reader: R, pattern_size: usize) -> io::Result<Option<usize>> {
+    if pattern_size == 0 {
+        return Ok(Some(0));
+    }
+    let mut window = vec![0u8; pattern_size];
+    let mut counts = [0usize; 256];
+    let mut distinct = 0;
+    for (i, byte) in BufReader::new(reader).bytes().enumerate() {
+        let byte = byte?;
+        if i >= pattern_size {
+            let old = window[i % pattern_size];
+            counts[old as usize] -= 1;
+            if counts[old as usize] == 0 {
+                distinct -= 1;
+            }
+        }
+        window[i % pattern_size] = byte;
+        counts[byte as usize] += 1;
+        if counts[byte as usize] == 1 {
+            distinct += 1;
+        }
+        if distinct == pattern_size {
+            return Ok(Some(i + 1));
+        }
+    }
+    Ok(None)
+}
+
+pub fn search_pattern(input: &str, pattern_size: usize) -> Option<usize> {
+    find_marker(input.as_bytes(), pattern_size).unwrap()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Packet,
+    Message,
+}
+
+// Byte ranges of a marker and of the data following it until the next marker of the same kind
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub kind: Kind,
+    pub marker: Range<usize>,
+    pub payload: Range<usize>,
+}
+
+pub struct Decoder {
+    packet_size: usize,
+    message_size: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        Decoder {
+            packet_size: 4,
+            message_size: 14,
+        }
+    }
+}
+
+impl Decoder {
+    pub fn new(packet_size: usize, message_size: usize) -> Result<Self, String> {
+        if packet_size == 0 || message_size == 0 {
+            return Err("Marker sizes must be at least 1".to_string());
+        }
+        Ok(Decoder {
+            packet_size,
+            message_size,
+        })
+    }
+
+    // Markers never overlap : the search for the next one starts right after the previous one
+    fn markers(data: &[u8], size: usize) -> Vec<Range<usize>> {
+        let mut result = vec![];
+        let mut start = 0;
+        while let Some(end) = find_marker(&data[start..], size).unwrap() {
+            result.push(start + end - size..start + end);
+            start += end;
+        }
+        result
+    }
+
+    // Packets and messages are two independent layers of the same stream, records are sorted by
+    // marker position
+    pub fn decode(&self, input: &str) -> Vec<Record> {
+        let data = input.as_bytes();
+        let mut result = vec![];
+        for (kind, size) in [
+            (Kind::Packet, self.packet_size),
+            (Kind::Message, self.message_size),
+        ] {
+            let markers = Decoder::markers(data, size);
+            let next_starts = markers.iter().skip(1).map(|m| m.start).chain([data.len()]);
+            for (marker, next_start) in markers.iter().zip(next_starts) {
+                result.push(Record {
+                    kind,
+                    marker: marker.clone(),
+                    payload: marker.end..next_start,
+                });
+            }
         }
+        result.sort_by_key(|r| (r.marker.start, r.kind == Kind::Message));
+        result
     }
-    0
+}
+
+pub fn decode(input: &str, packet_size: usize, message_size: usize) -> Result<String, String> {
+    let decoder = Decoder::new(packet_size, message_size)?;
+    Ok(decoder
+        .decode(input.trim_end())
+        .iter()
+        .map(|r| {
+            format!(
+                "{:?} {} [{}] {}",
+                r.kind,
+                r.marker.start,
+                String::from_utf8_lossy(&input.as_bytes()[r.marker.clone()]),
+                String::from_utf8_lossy(&input.as_bytes()[r.payload.clone()])
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n"))
 }
 
 pub fn part_1(input: &str) -> usize {
-    search_pattern(input, 4)
+    search_pattern(input, 4).expect("No start-of-packet marker")
 }
 
 pub fn part_2(input: &str) -> usize {
-    search_pattern(input, 14)
+    search_pattern(input, 14).expect("No start-of-message marker")
 }
 
 #[cfg(test)]
@@ -71,6 +188,78 @@ mod tests {
         }
     }
     
+    #[test]
+    fn test_search_pattern() {
+        assert_eq!(search_pattern("abc", 4), None);
+        assert_eq!(search_pattern("abca", 4), None);
+        assert_eq!(search_pattern("abcd", 4), Some(4));
+        assert_eq!(search_pattern("aab", 1), Some(1));
+        assert_eq!(search_pattern("aab", 0), Some(0));
+        assert_eq!(search_pattern("", 1), None);
+        let alphabet = (b'a'..=b'z').map(char::from).collect::<String>();
+        assert_eq!(search_pattern(&format!("zz{alphabet}"), 26), Some(27));
+        assert_eq!(search_pattern(&alphabet, 27), None);
+    }
+
+    #[test]
+    fn test_find_marker_in_stream() {
+        let stream = io::repeat(b'a').take(1_000_000).chain(&b"bcd"[..]);
+        assert_eq!(find_marker(stream, 4).unwrap(), Some(1_000_003));
+        // Stops reading as soon as the marker is found
+        let endless = b"abcd".chain(io::repeat(b'a'));
+        assert_eq!(find_marker(endless, 4).unwrap(), Some(4));
+    }
+
+    #[test]
+    fn test_decoder() {
+        let records = Decoder::default().decode(TEST_INPUT_1);
+        let packets = records
+            .iter()
+            .filter(|r| r.kind == Kind::Packet)
+            .map(|r| &TEST_INPUT_1[r.marker.clone()])
+            .collect::<Vec<&str>>();
+        assert_eq!(packets, ["jpqm", "gblj", "sphd", "ztnv", "jfqw", "rcgs"]);
+        assert_eq!(
+            records[1],
+            Record {
+                kind: Kind::Message,
+                marker: 5..19,
+                payload: 19..30
+            }
+        );
+        assert_eq!(records.last().unwrap().payload, 27..30);
+        for test_data in TEST_DATA {
+            let records = Decoder::default().decode(test_data.input);
+            let first = |kind| records.iter().find(|r| r.kind == kind).unwrap().marker.end;
+            assert_eq!(first(Kind::Packet), test_data.output_part_1);
+            assert_eq!(first(Kind::Message), test_data.output_part_2);
+        }
+    }
+
+    #[test]
+    fn test_decoder_sizes() {
+        assert_eq!(
+            decode("aabcaabbcd", 2, 3),
+            Ok([
+                "Packet 1 [ab] ",
+                "Message 1 [abc] aab",
+                "Packet 3 [ca] ",
+                "Packet 5 [ab] ",
+                "Packet 7 [bc] d",
+                "Message 7 [bcd] "
+            ]
+            .join("\n"))
+        );
+        assert!(Decoder::new(0, 14).is_err());
+        assert_eq!(decode("", 4, 14), Ok(String::new()));
+        // Markers are found on bytes, a character may be split between a marker and its payload
+        assert_eq!(
+            decode("abcé", 4, 14),
+            Ok("Packet 0 [abc\u{FFFD}] \u{FFFD}".to_string())
+        );
+        assert_eq!(decode("éa", 3, 14), Ok("Packet 0 [éa] ".to_string()));
+    }
+
     #[test]
     fn test_part_2() {
         for test_data in TEST_DATA {
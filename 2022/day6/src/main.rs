@@ -2,7 +2,31 @@ mod implementation;
 use implementation::*;
 
 fn main() {
-    let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    if let Some(i) = args.iter().position(|x| x == "--window") {
+        // --window N reads the datastream from the standard input
+        let size = args
+            .get(i + 1)
+            .and_then(|x| x.parse::<usize>().ok())
+            .unwrap_or_else(|| panic!("Invalid value for --window"));
+        match find_marker(std::io::stdin().lock(), size).unwrap() {
+            Some(position) => println!("Marker of size {size} found at {position}"),
+            None => println!("No marker of size {size}"),
+        }
+    } else if let Some(i) = args.iter().position(|x| x == "--decode") {
+        // --decode [PACKET_SIZE MESSAGE_SIZE]
+        let size = |offset: usize, default: usize| {
+            args.get(i + offset).map_or(default, |x| {
+                x.parse::<usize>().expect("Invalid marker size")
+            })
+        };
+        match decode(include_str!("input.txt"), size(1, 4), size(2, 14)) {
+            Ok(records) => println!("{records}"),
+            Err(e) => println!("{e}"),
+        }
+    } else {
+        let input = include_str!("input.txt");
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+    }
 }
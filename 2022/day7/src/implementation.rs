@@ -1,4 +1,11 @@
-use std::collections::VecDeque;
+use std::{
+    collections::HashSet,
+    env,
+    fmt::Display,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 enum EntryType {
     File(String, usize),
@@ -30,6 +37,18 @@ impl Entry {
         }
     }
 
+    fn is_dir(&self) -> bool {
+        matches!(self.entry, EntryType::Directory(_))
+    }
+
+    fn child(&self, name: &str) -> Option<&Entry> {
+        self.children.iter().find(|x| x.name() == name)
+    }
+
+    fn child_mut(&mut self, name: &str) -> Option<&mut Entry> {
+        self.children.iter_mut().find(|x| x.name() == name)
+    }
+
     fn get_total_size(&self) -> usize {
         if let EntryType::File(_, s) = self.entry {
             s
@@ -55,61 +74,533 @@ impl Entry {
         result.append(&mut child_directories_sizes);
         result
     }
+
+    fn find<'a>(
+        &'a self,
+        path: &str,
+        predicate: &impl Fn(&str, &Entry) -> bool,
+        result: &mut Vec<(String, &'a Entry)>,
+    ) {
+        if predicate(path, self) {
+            result.push((String::from(path), self));
+        }
+        for child in self.children.iter() {
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), child.name());
+            child.find(&child_path, predicate, result);
+        }
+    }
 }
 
-fn walk<'a>(root: &'a mut Entry, lines: &'a mut VecDeque<&str>) {
-    while let Some(line) = lines.pop_front() {
-        let content: Vec<&str> = line.split_ascii_whitespace().collect();
-        if content[0].starts_with('$') {
-            match content[1] {
-                "ls" => continue,
-                "cd" => {
-                    if content[2].starts_with("..") {
-                        return;
+impl Entry {
+    fn render_tree(&self, depth: usize, result: &mut String) {
+        if depth > 0 {
+            result.push('\n');
+        }
+        result.push_str(&"  ".repeat(depth));
+        match &self.entry {
+            EntryType::File(n, s) => result.push_str(&format!("- {n} (file, size={s})")),
+            EntryType::Directory(n) => result.push_str(&format!("- {n} (dir)")),
+        }
+        for child in self.children.iter() {
+            child.render_tree(depth + 1, result);
+        }
+    }
+
+    fn to_json(&self, depth: usize) -> String {
+        let indent = "  ".repeat(depth + 1);
+        let mut fields = vec![
+            format!("{indent}\"name\": {}", json_string(self.name())),
+            format!(
+                "{indent}\"type\": \"{}\"",
+                if self.is_dir() { "dir" } else { "file" }
+            ),
+            format!("{indent}\"size\": {}", self.get_total_size()),
+        ];
+        if self.is_dir() {
+            let children = self
+                .children
+                .iter()
+                .map(|x| format!("{indent}  {}", x.to_json(depth + 2)))
+                .collect::<Vec<_>>();
+            fields.push(if children.is_empty() {
+                format!("{indent}\"children\": []")
+            } else {
+                format!(
+                    "{indent}\"children\": [\n{}\n{indent}]",
+                    children.join(",\n")
+                )
+            });
+        }
+        format!("{{\n{}\n{}}}", fields.join(",\n"), "  ".repeat(depth))
+    }
+}
+
+impl Entry {
+    // Collects the paths of the selected files, replaced by their directory when all of its
+    // content is selected. Returns whether the whole entry is selected
+    fn select(&self, path: &str, selected: &HashSet<&str>, result: &mut Vec<String>) -> bool {
+        if !self.is_dir() {
+            let whole = selected.contains(path) || self.get_total_size() == 0;
+            if whole && self.get_total_size() > 0 {
+                result.push(String::from(path));
+            }
+            return whole;
+        }
+        let mut children = vec![];
+        let mut whole = true;
+        for child in self.children.iter() {
+            let child_path = format!("{}/{}", path.trim_end_matches('/'), child.name());
+            whole &= child.select(&child_path, selected, &mut children);
+        }
+        if whole && self.get_total_size() > 0 {
+            result.push(String::from(path));
+        } else {
+            result.append(&mut children);
+        }
+        whole
+    }
+}
+
+struct DeletionPlan {
+    paths: Vec<String>,
+    freed: usize,
+    free_after: usize,
+    target_free: usize,
+}
+
+impl Display for DeletionPlan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for path in self.paths.iter() {
+            writeln!(f, "rm -r {path}")?;
+        }
+        write!(
+            f,
+            "Freed {}, free space is now {} for {} needed",
+            self.freed, self.free_after, self.target_free
+        )
+    }
+}
+
+// Same listing as the one given in the puzzle statement
+impl Display for Entry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut result = String::new();
+        self.render_tree(0, &mut result);
+        write!(f, "{result}")
+    }
+}
+
+fn json_string(value: &str) -> String {
+    let mut result = String::from('"');
+    for c in value.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            c if c.is_control() => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
+
+// Size rounded up with a unit suffix, as printed by du -h
+fn human_size(size: usize) -> String {
+    let mut value = size as f64;
+    for unit in ["", "K", "M", "G", "T"] {
+        if value < 1024.0 || unit == "T" {
+            return if unit.is_empty() {
+                format!("{size}")
+            } else if value < 10.0 {
+                format!("{:.1}{unit}", (value * 10.0).ceil() / 10.0)
+            } else {
+                format!("{}{unit}", value.ceil())
+            };
+        }
+        value /= 1024.0;
+    }
+    unreachable!()
+}
+
+struct FileSystem {
+    root: Entry,
+    // Names of the directories leading from the root to the current directory
+    cwd: Vec<String>,
+    listing: bool,
+    // Directories whose content was given by an ls, so their children are all known
+    listed: HashSet<Vec<String>>,
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        FileSystem {
+            root: Entry::new_dir("/"),
+            cwd: vec![],
+            listing: false,
+            listed: HashSet::new(),
+        }
+    }
+}
+
+impl FileSystem {
+    fn resolve(&self, path: &str) -> Vec<String> {
+        let mut result = if path.starts_with('/') {
+            vec![]
+        } else {
+            self.cwd.clone()
+        };
+        for component in path.split('/').filter(|x| !x.is_empty()) {
+            match component {
+                "." => {}
+                ".." => {
+                    result.pop();
+                }
+                name => result.push(String::from(name)),
+            }
+        }
+        result
+    }
+
+    fn current_dir_mut(&mut self) -> &mut Entry {
+        let mut dir = &mut self.root;
+        for name in self.cwd.iter() {
+            dir = dir.child_mut(name).unwrap();
+        }
+        dir
+    }
+
+    // Directories missing from a parent which was not listed yet are created on the way, since the
+    // log proves they exist; a listed parent must already know them
+    fn cd(&mut self, path: &str) -> Result<(), String> {
+        let target = self.resolve(path);
+        let mut dir = &mut self.root;
+        for (depth, name) in target.iter().enumerate() {
+            if dir.child(name).is_none() {
+                if self.listed.contains(&target[..depth]) {
+                    return Err(format!(
+                        "Inconsistent listing of {name} in /{}",
+                        target[..depth].join("/")
+                    ));
+                }
+                dir.children.push(Entry::new_dir(name));
+            }
+            dir = dir.child_mut(name).unwrap();
+            if !dir.is_dir() {
+                return Err(format!("Can not cd into file {name}"));
+            }
+        }
+        self.cwd = target;
+        self.listing = false;
+        Ok(())
+    }
+
+    fn ls(&mut self) {
+        self.listing = true;
+        self.listed.insert(self.cwd.clone());
+    }
+
+    // Listing the same directory again only adds the entries which were not known yet
+    fn add(&mut self, entry: Entry) -> Result<(), String> {
+        // Names end up as paths when materializing, so they must stay inside their directory
+        if matches!(entry.name(), "" | "." | "..") || entry.name().contains('/') {
+            return Err(format!("Invalid entry name \"{}\"", entry.name()));
+        }
+        if !self.listing {
+            return Err(format!(
+                "Entry {} listed outside of ls output",
+                entry.name()
+            ));
+        }
+        let path = format!("/{}", self.cwd.join("/"));
+        let dir = self.current_dir_mut();
+        match dir.child(entry.name()) {
+            None => dir.children.push(entry),
+            Some(known) => match (&known.entry, &entry.entry) {
+                (EntryType::Directory(_), EntryType::Directory(_)) => {}
+                (EntryType::File(_, a), EntryType::File(_, b)) if a == b => {}
+                _ => {
+                    return Err(format!(
+                        "Inconsistent listing of {} in {path}",
+                        entry.name()
+                    ))
+                }
+            },
+        }
+        Ok(())
+    }
+
+    fn lookup(&self, path: &str) -> Option<&Entry> {
+        let mut entry = &self.root;
+        for name in self.resolve(path) {
+            entry = entry.child(&name)?;
+        }
+        Some(entry)
+    }
+
+    fn du(&self, path: &str) -> Option<usize> {
+        self.lookup(path).map(|x| x.get_total_size())
+    }
+
+    // Entries matching the predicate, which is given the absolute path and the entry itself
+    fn find(&self, predicate: impl Fn(&str, &Entry) -> bool) -> Vec<(String, &Entry)> {
+        let mut result = vec![];
+        self.root.find("/", &predicate, &mut result);
+        result
+    }
+
+    // Deleting a directory frees as much as deleting all of its files, so the smallest deletion
+    // freeing enough space is a subset-sum over the files. The selected files are then grouped
+    // back into whole directories when all of their content is deleted
+    fn plan_deletion(&self, disk_size: usize, target_free: usize) -> Option<DeletionPlan> {
+        let used = self.root.get_total_size();
+        let free = disk_size.checked_sub(used)?;
+        let needed = target_free.saturating_sub(free);
+        let files = self
+            .find(|_, x| !x.is_dir() && x.get_total_size() > 0)
+            .into_iter()
+            .map(|(p, x)| (p, x.get_total_size()))
+            .collect::<Vec<_>>();
+        let total = files.iter().map(|(_, s)| *s).sum::<usize>();
+        let keep = total.checked_sub(needed)?;
+        // Either look for the smallest sum of deleted files above the needed size, or for the
+        // biggest sum of kept files below what may be kept, whichever needs the smallest table.
+        // Adding files one by one crosses the needed size by less than the biggest file
+        let biggest = files.iter().map(|(_, s)| *s).max().unwrap_or(0);
+        let keeping = keep < needed + biggest;
+        let bound = if keeping { keep } else { needed + biggest };
+        // Reachable sums are kept in a bitset, and first_item[v] is the index of the file which
+        // first made the sum v reachable
+        let mut reach = vec![0u64; bound / 64 + 1];
+        reach[0] = 1;
+        let mut first_item = vec![0u32; bound + 1];
+        for (i, (_, size)) in files.iter().enumerate() {
+            let (shift_words, shift_bits) = (size / 64, size % 64);
+            for j in (shift_words..reach.len()).rev() {
+                let mut shifted = reach[j - shift_words] << shift_bits;
+                if shift_bits > 0 && j > shift_words {
+                    shifted |= reach[j - shift_words - 1] >> (64 - shift_bits);
+                }
+                let mut new = shifted & !reach[j];
+                reach[j] |= new;
+                while new != 0 {
+                    let v = j * 64 + new.trailing_zeros() as usize;
+                    if v <= bound {
+                        first_item[v] = i as u32;
                     }
-                    let child_to_walk = root
-                        .children
-                        .iter_mut()
-                        .find(|x| {
-                            if let EntryType::Directory(n) = &x.entry {
-                                n == content[2]
-                            } else {
-                                false
-                            }
-                        })
-                        .unwrap();
-                    walk(child_to_walk, lines)
+                    new &= new - 1;
                 }
-                _ => panic!("Unexpected command {}", content[1]),
             }
-        } else if content[0] == "dir" {
-            root.children.push(Entry::new_dir(content[1]));
+        }
+        let reachable = |v: usize| reach[v / 64] & (1 << (v % 64)) != 0;
+        let mut v = if keeping {
+            (0..=keep).rev().find(|&v| reachable(v))?
         } else {
-            root.children
-                .push(Entry::new_file(content[1], content[0].parse().unwrap()));
+            (needed..=bound).find(|&v| reachable(v))?
+        };
+        let freed = if keeping { total - v } else { v };
+        let mut chain = HashSet::new();
+        while v > 0 {
+            let (path, size) = &files[first_item[v] as usize];
+            chain.insert(path.as_str());
+            v -= size;
+        }
+        let selected = files
+            .iter()
+            .map(|(p, _)| p.as_str())
+            .filter(|p| chain.contains(p) != keeping)
+            .collect::<HashSet<_>>();
+        let mut paths = vec![];
+        self.root.select("/", &selected, &mut paths);
+        Some(DeletionPlan {
+            paths,
+            freed,
+            free_after: free + freed,
+            target_free,
+        })
+    }
+
+    // Directories sorted from the biggest to the smallest one, as du | sort -rn would print them
+    fn du_table(&self, human: bool) -> String {
+        let mut directories = self
+            .find(|_, x| x.is_dir())
+            .into_iter()
+            .map(|(p, x)| (x.get_total_size(), p))
+            .collect::<Vec<_>>();
+        directories.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        directories
+            .into_iter()
+            .map(|(s, p)| {
+                if human {
+                    format!("{}\t{p}", human_size(s))
+                } else {
+                    format!("{s}\t{p}")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+fn walk(input: &str) -> Result<FileSystem, String> {
+    let mut fs = FileSystem::default();
+    for line in input.lines() {
+        let content: Vec<&str> = line.split_ascii_whitespace().collect();
+        match content.as_slice() {
+            ["$", "cd", path] => fs.cd(path)?,
+            ["$", "ls"] => fs.ls(),
+            ["$", command, ..] => return Err(format!("Unexpected command {command}")),
+            ["dir", name] => fs.add(Entry::new_dir(name))?,
+            [size, name] => {
+                let size = size
+                    .parse()
+                    .map_err(|_| format!("Invalid file size in \"{line}\""))?;
+                fs.add(Entry::new_file(name, size))?
+            }
+            _ => return Err(format!("Malformed line \"{line}\"")),
         }
     }
+    Ok(fs)
+}
+
+impl Entry {
+    // Scans a real directory, children being sorted by name so that scans are reproducible
+    fn from_dir(path: &Path) -> io::Result<Entry> {
+        let mut entry = Entry::new_dir(&entry_name(path)?);
+        let mut children = fs::read_dir(path)?.collect::<io::Result<Vec<_>>>()?;
+        children.sort_by_key(|x| x.file_name());
+        for child in children {
+            // Links can not be described by a terminal log, so they are skipped
+            let file_type = child.file_type()?;
+            if file_type.is_dir() {
+                entry.children.push(Entry::from_dir(&child.path())?);
+            } else if file_type.is_file() {
+                entry.children.push(Entry::new_file(
+                    &entry_name(&child.path())?,
+                    child.metadata()?.len() as usize,
+                ));
+            }
+        }
+        Ok(entry)
+    }
+
+    // Terminal log listing each directory once, in the same pre-order as the puzzle input
+    fn transcript(&self) -> String {
+        let mut result = vec![String::from("$ cd /")];
+        self.transcribe(&mut result);
+        result.join("\n")
+    }
+
+    fn transcribe(&self, result: &mut Vec<String>) {
+        result.push(String::from("$ ls"));
+        for child in self.children.iter() {
+            result.push(match &child.entry {
+                EntryType::File(n, s) => format!("{s} {n}"),
+                EntryType::Directory(n) => format!("dir {n}"),
+            });
+        }
+        for child in self.children.iter().filter(|x| x.is_dir()) {
+            result.push(format!("$ cd {}", child.name()));
+            child.transcribe(result);
+            result.push(String::from("$ cd .."));
+        }
+    }
+
+    fn materialize(&self, path: &Path) -> io::Result<()> {
+        for child in self.children.iter() {
+            let child_path = path.join(child.name());
+            match &child.entry {
+                EntryType::File(_, s) => fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(child_path)?
+                    .set_len(*s as u64)?,
+                EntryType::Directory(_) => {
+                    fs::create_dir(&child_path)?;
+                    child.materialize(&child_path)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Names with whitespaces can not be written in a terminal log
+fn entry_name(path: &Path) -> io::Result<String> {
+    let name = match path.file_name() {
+        Some(name) => name.to_str(),
+        None => Some("/"),
+    };
+    name.filter(|x| !x.is_empty() && !x.contains(char::is_whitespace))
+        .map(String::from)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Can not transcribe {}", path.display()),
+            )
+        })
+}
+
+// Creates the files of the log in a new temporary directory. Files are sparse, so that they
+// take almost no disk space whatever their size
+fn materialize(file_system: &FileSystem) -> io::Result<PathBuf> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!(
+        "day7-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir(&path)?;
+    file_system.root.materialize(&path)?;
+    Ok(path)
+}
+
+pub fn transcript(path: &str) -> String {
+    Entry::from_dir(Path::new(path)).unwrap().transcript()
+}
+
+pub fn materialize_log(input: &str) -> String {
+    let path = materialize(&walk(input).unwrap()).unwrap();
+    path.display().to_string()
+}
+
+pub fn tree(input: &str) -> String {
+    format!("{}", walk(input).unwrap().root)
+}
+
+pub fn du(input: &str, human: bool) -> String {
+    walk(input).unwrap().du_table(human)
+}
+
+pub fn json(input: &str) -> String {
+    walk(input).unwrap().root.to_json(0)
+}
+
+const TOTAL_SIZE: usize = 70000000;
+const OTA_NEEDED_SPACE: usize = 30000000;
+
+pub fn plan(input: &str, disk_size: Option<usize>, target_free: Option<usize>) -> String {
+    match walk(input).unwrap().plan_deletion(
+        disk_size.unwrap_or(TOTAL_SIZE),
+        target_free.unwrap_or(OTA_NEEDED_SPACE),
+    ) {
+        Some(plan) => format!("{plan}"),
+        None => String::from("No deletion can free enough space"),
+    }
 }
 
 pub fn part_1(input: &str) -> usize {
-    let mut root = Entry::new_dir("/");
-    let mut lines: VecDeque<&str> = input.lines().skip(1).collect();
-    walk(&mut root, &mut lines);
-    root.get_directories_size()
+    let fs = walk(input).unwrap();
+    fs.find(|_, x| x.is_dir() && x.get_total_size() < 100000)
         .into_iter()
-        .filter(|&x| x < 100000)
+        .map(|(_, x)| x.get_total_size())
         .sum()
 }
 
 pub fn part_2(input: &str) -> usize {
-    const TOTAL_SIZE: usize = 70000000;
-    const OTA_NEEDED_SPACE: usize = 30000000;
-    let mut root = Entry::new_dir("/");
-    let mut lines: VecDeque<&str> = input.lines().skip(1).collect();
-    walk(&mut root, &mut lines);
-    let mut sizes = root.get_directories_size();
+    let fs = walk(input).unwrap();
+    let mut sizes = fs.root.get_directories_size();
     sizes.sort();
-    let unused_space = TOTAL_SIZE - sizes.pop().unwrap();
+    let unused_space = TOTAL_SIZE - fs.du("/").unwrap();
     let needed_space = OTA_NEEDED_SPACE - unused_space;
     *sizes.iter().find(|&x| x >= &needed_space).unwrap()
 }
@@ -141,6 +632,340 @@ $ ls
 8033020 d.log
 5626152 d.ext
 7214296 k";
+    const TEST_OUTPUT_TREE: &str = "\
+- / (dir)
+  - a (dir)
+    - e (dir)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)";
+
+    #[test]
+    fn test_lookup() {
+        let fs = walk(TEST_INPUT).unwrap();
+        assert_eq!(fs.du("/"), Some(48381165));
+        assert_eq!(fs.du("/a"), Some(94853));
+        assert_eq!(fs.du("/a/e"), Some(584));
+        assert_eq!(fs.du("/d/k"), Some(7214296));
+        assert_eq!(fs.du("/d/../a/./e/i"), Some(584));
+        assert!(fs.lookup("/a/e").unwrap().is_dir());
+        assert!(fs.lookup("/x").is_none());
+        // The log ends in /d, relative paths start from there
+        assert_eq!(fs.du("k"), Some(7214296));
+        assert_eq!(fs.du("../a/e"), Some(584));
+    }
+
+    #[test]
+    fn test_find() {
+        let fs = walk(TEST_INPUT).unwrap();
+        let found = fs.find(|_, x| x.name().starts_with("d."));
+        assert_eq!(
+            found.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(),
+            vec!["/d/d.log", "/d/d.ext"]
+        );
+        let found = fs.find(|_, x| !x.is_dir() && x.get_total_size() > 8000000);
+        assert_eq!(
+            found.iter().map(|(p, _)| p.as_str()).collect::<Vec<_>>(),
+            vec!["/b.txt", "/c.dat", "/d/d.log"]
+        );
+        let found = fs.find(|p, _| p.starts_with("/a/"));
+        assert_eq!(found.len(), 5);
+    }
+
+    #[test]
+    fn test_unordered_log() {
+        let fs = walk(
+            "$ cd /a/e
+$ ls
+584 i
+$ cd /
+$ ls
+dir a
+14848514 b.txt
+$ cd a
+$ ls
+dir e
+29116 f
+$ ls
+29116 f
+$ cd e
+$ ls
+584 i",
+        )
+        .unwrap();
+        assert_eq!(fs.du("/"), Some(14848514 + 29116 + 584));
+        assert_eq!(fs.lookup("/a").unwrap().children.len(), 2);
+        assert_eq!(fs.lookup("/a/e").unwrap().children.len(), 1);
+    }
+
+    #[test]
+    fn test_cd_beyond_root() {
+        let fs = walk("$ cd ..\n$ cd ../a\n$ ls\n12 b").unwrap();
+        assert_eq!(fs.du("/a/b"), Some(12));
+    }
+
+    #[test]
+    fn test_invalid_logs() {
+        assert_eq!(
+            walk("$ cd /\n$ ls\n12 a\n$ ls\n13 a").err(),
+            Some(String::from("Inconsistent listing of a in /"))
+        );
+        assert_eq!(
+            walk("$ cd /\n$ ls\n12 a\n$ ls\ndir a").err(),
+            Some(String::from("Inconsistent listing of a in /"))
+        );
+        assert_eq!(
+            walk("$ cd /\n$ ls\n12 a\n$ cd a").err(),
+            Some(String::from("Can not cd into file a"))
+        );
+        assert_eq!(
+            walk("$ cd /\n$ ls\ndir a\n10 f\n$ cd b\n$ ls\n5 g").err(),
+            Some(String::from("Inconsistent listing of b in /"))
+        );
+        assert_eq!(
+            walk("$ cd /\n$ ls\ndir a\n$ cd a\n$ ls\n$ cd /a/c").err(),
+            Some(String::from("Inconsistent listing of c in /a"))
+        );
+        assert_eq!(
+            walk("$ cd /\n12 a").err(),
+            Some(String::from("Entry a listed outside of ls output"))
+        );
+        assert_eq!(
+            walk("$ rm -rf /").err(),
+            Some(String::from("Unexpected command rm"))
+        );
+        assert_eq!(
+            walk("$ ls\nbig a").err(),
+            Some(String::from("Invalid file size in \"big a\""))
+        );
+        assert_eq!(
+            walk("$ ls\n12").err(),
+            Some(String::from("Malformed line \"12\""))
+        );
+        for name in ["..", ".", "../escaped", "a/b"] {
+            assert_eq!(
+                walk(&format!("$ cd /\n$ ls\n12 {name}")).err(),
+                Some(format!("Invalid entry name \"{name}\""))
+            );
+        }
+        assert_eq!(
+            walk("$ cd /\n$ ls\ndir ..").err(),
+            Some(String::from("Invalid entry name \"..\""))
+        );
+    }
+
+    #[test]
+    fn test_tree() {
+        assert_eq!(tree(TEST_INPUT), TEST_OUTPUT_TREE);
+    }
+
+    #[test]
+    fn test_du() {
+        assert_eq!(
+            du(TEST_INPUT, false),
+            "48381165\t/\n24933642\t/d\n94853\t/a\n584\t/a/e"
+        );
+        assert_eq!(du(TEST_INPUT, true), "47M\t/\n24M\t/d\n93K\t/a\n584\t/a/e");
+        let mut sizes = walk(TEST_INPUT).unwrap().root.get_directories_size();
+        sizes.sort_by(|a, b| b.cmp(a));
+        assert_eq!(sizes, vec![48381165, 24933642, 94853, 584]);
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(0), "0");
+        assert_eq!(human_size(1023), "1023");
+        assert_eq!(human_size(1024), "1.0K");
+        assert_eq!(human_size(1025), "1.1K");
+        assert_eq!(human_size(10 * 1024), "10K");
+        assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0G");
+    }
+
+    #[test]
+    fn test_json() {
+        let fs = walk("$ cd /\n$ ls\ndir a\n12 \"b\\c\"\n$ cd a\n$ ls").unwrap();
+        assert_eq!(
+            fs.root.to_json(0),
+            r#"{
+  "name": "/",
+  "type": "dir",
+  "size": 12,
+  "children": [
+    {
+      "name": "a",
+      "type": "dir",
+      "size": 0,
+      "children": []
+    },
+    {
+      "name": "\"b\\c\"",
+      "type": "file",
+      "size": 12
+    }
+  ]
+}"#
+        );
+    }
+
+    #[test]
+    fn test_plan_deletion() {
+        let fs = walk(TEST_INPUT).unwrap();
+        let plan = fs.plan_deletion(TOTAL_SIZE, OTA_NEEDED_SPACE).unwrap();
+        assert_eq!(plan.paths, vec!["/c.dat"]);
+        assert_eq!(plan.freed, 8504156);
+        assert_eq!(
+            format!("{plan}"),
+            "rm -r /c.dat\nFreed 8504156, free space is now 30122991 for 30000000 needed"
+        );
+        let plan = fs.plan_deletion(TOTAL_SIZE, 1000).unwrap();
+        assert!(plan.paths.is_empty());
+        assert_eq!(plan.free_after, TOTAL_SIZE - 48381165);
+        assert!(fs.plan_deletion(TOTAL_SIZE, TOTAL_SIZE + 1).is_none());
+        assert!(fs.plan_deletion(1000, 0).is_none());
+    }
+
+    #[test]
+    fn test_plan_whole_directories() {
+        let fs = walk(TEST_INPUT).unwrap();
+        // Freeing exactly the content of /a and /d/k groups the files of /a back together
+        let used = 48381165;
+        let plan = fs.plan_deletion(used, 94853 + 7214296).unwrap();
+        assert_eq!(plan.paths, vec!["/a", "/d/k"]);
+        let plan = fs.plan_deletion(used, used).unwrap();
+        assert_eq!(plan.paths, vec!["/"]);
+    }
+
+    #[test]
+    fn test_plan_is_optimal() {
+        let fs = walk(
+            "$ cd /
+$ ls
+dir a
+dir b
+700 x
+$ cd a
+$ ls
+310 y
+290 z
+$ cd /b
+$ ls
+450 w
+120 v
+80 u",
+        )
+        .unwrap();
+        let sizes = [700, 310, 290, 450, 120, 80];
+        let used: usize = sizes.iter().sum();
+        for target in (0..=used).step_by(10) {
+            let best = (0..1 << sizes.len())
+                .map(|mask: usize| {
+                    (0..sizes.len())
+                        .filter(|i| mask & (1 << i) != 0)
+                        .map(|i| sizes[i])
+                        .sum::<usize>()
+                })
+                .filter(|&x| x >= target)
+                .min()
+                .unwrap();
+            let plan = fs.plan_deletion(used, target).unwrap();
+            assert_eq!(plan.freed, best);
+            assert_eq!(
+                plan.paths.iter().map(|p| fs.du(p).unwrap()).sum::<usize>(),
+                best
+            );
+        }
+    }
+
+    fn sort(entry: &mut Entry) {
+        entry.children.sort_by(|a, b| a.name().cmp(b.name()));
+        entry.children.iter_mut().for_each(sort);
+    }
+
+    fn random_tree(random: &mut impl FnMut(usize) -> usize, depth: usize) -> Entry {
+        let mut entry = Entry::new_dir(&format!("d{}", random(1000)));
+        for i in 0..random(6) {
+            if depth < 4 && random(3) == 0 {
+                let mut child = random_tree(random, depth + 1);
+                child.entry = EntryType::Directory(format!("{i}.{}", child.name()));
+                entry.children.push(child);
+            } else {
+                let name = format!("{i}.f{}", random(1000));
+                entry
+                    .children
+                    .push(Entry::new_file(&name, random(50000000)));
+            }
+        }
+        entry
+    }
+
+    #[test]
+    fn test_transcript() {
+        let fs = walk(TEST_INPUT).unwrap();
+        assert_eq!(fs.root.transcript(), format!("{TEST_INPUT}\n$ cd .."));
+    }
+
+    #[test]
+    fn test_materialize_round_trip() {
+        let mut fs = walk(TEST_INPUT).unwrap();
+        let path = materialize(&fs).unwrap();
+        assert_eq!(
+            fs::metadata(path.join("d").join("d.log")).unwrap().len(),
+            8033020
+        );
+        let mut scanned = Entry::from_dir(&path).unwrap();
+        fs::remove_dir_all(&path).unwrap();
+        scanned.entry = EntryType::Directory(String::from("/"));
+        sort(&mut fs.root);
+        assert_eq!(scanned.transcript(), fs.root.transcript());
+        let scanned = walk(&scanned.transcript()).unwrap();
+        assert_eq!(
+            scanned.root.get_directories_size(),
+            fs.root.get_directories_size()
+        );
+    }
+
+    #[test]
+    fn test_random_round_trips() {
+        let mut seed: usize = 7;
+        let mut random = |max: usize| {
+            seed = (seed * 1103515245 + 12345) % (1 << 31);
+            seed % max
+        };
+        for _ in 0..20 {
+            let mut root = random_tree(&mut random, 0);
+            root.entry = EntryType::Directory(String::from("/"));
+            sort(&mut root);
+            let fs = walk(&root.transcript()).unwrap();
+            assert_eq!(fs.root.transcript(), root.transcript());
+            let path = materialize(&fs).unwrap();
+            let mut scanned = Entry::from_dir(&path).unwrap();
+            fs::remove_dir_all(&path).unwrap();
+            scanned.entry = EntryType::Directory(String::from("/"));
+            assert_eq!(scanned.transcript(), root.transcript());
+            assert_eq!(fs.du("/"), Some(root.get_total_size()));
+        }
+    }
+
+    #[test]
+    fn test_scan_invalid_name() {
+        let fs = walk("$ cd /\n$ ls\n12 a").unwrap();
+        let path = materialize(&fs).unwrap();
+        fs::File::create(path.join("b c")).unwrap();
+        let scanned = Entry::from_dir(&path);
+        fs::remove_dir_all(&path).unwrap();
+        assert_eq!(
+            scanned.err().map(|x| x.kind()),
+            Some(io::ErrorKind::InvalidData)
+        );
+    }
 
     #[test]
     fn test_part_1() {
@@ -3,6 +3,19 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let size_arg = |i: usize| args.get(i).map(|x| x.parse().expect("Invalid size"));
+    match args.get(1).map(|x| x.as_str()) {
+        Some("--tree") => println!("{}", tree(input)),
+        Some("--du") => println!("{}", du(input, false)),
+        Some("--du-h") => println!("{}", du(input, true)),
+        Some("--json") => println!("{}", json(input)),
+        Some("--plan") => println!("{}", plan(input, size_arg(2), size_arg(3))),
+        Some("--transcript") => println!("{}", transcript(args.get(2).expect("Missing path"))),
+        Some("--materialize") => println!("{}", materialize_log(input)),
+        _ => {
+            println!("Part 1 answer : {}", part_1(input));
+            println!("Part 2 answer : {}", part_2(input));
+        }
+    }
 }
@@ -1,3 +1,5 @@
+use std::{collections::HashSet, fs, io, path::Path};
+
 struct Tree {
     size: usize,
     seen: bool,
@@ -8,25 +10,61 @@ impl From<usize> for Tree {
         Tree { size, seen: false }
     }
 }
+
+// Eye position, possibly outside of the grid, looking from a given height
+#[derive(Clone, Copy)]
+struct Viewpoint {
+    line: isize,
+    column: isize,
+    height: usize,
+}
+
+#[derive(Clone, Copy)]
+pub enum Sight {
+    // Looking along the eight compass directions only
+    Compass,
+    // Casting a ray from the eye towards the top of every tree
+    Rays,
+}
+
+const COMPASS: [(isize, isize); 8] = [
+    (-1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+    (1, 0),
+    (1, -1),
+    (0, -1),
+    (-1, -1),
+];
+
 struct Forest {
     grid: Vec<Vec<Tree>>,
-    size: usize,
+    width: usize,
+    height: usize,
 }
 
 impl Forest {
     fn new(input: &str) -> Self {
         let mut result = Forest {
             grid: vec![],
-            size: input.lines().next().unwrap().trim().len(),
+            width: input.lines().next().unwrap().trim().len(),
+            height: 0,
         };
         for line in input.lines() {
-            result.grid.push(
-                line.trim()
-                    .chars()
-                    .map(|x| Tree::from(x.to_digit(10).unwrap() as usize))
-                    .collect::<Vec<Tree>>(),
-            )
+            let trees = line
+                .trim()
+                .chars()
+                .map(|x| Tree::from(x.to_digit(10).unwrap() as usize))
+                .collect::<Vec<Tree>>();
+            assert_eq!(
+                trees.len(),
+                result.width,
+                "All lines must have the same length"
+            );
+            result.grid.push(trees)
         }
+        result.height = result.grid.len();
         result
     }
 
@@ -49,16 +87,16 @@ impl Forest {
             }
         }
         //Top to bottom
-        for column in 0..self.size {
+        for column in 0..self.width {
             let mut max = None;
-            for line in 0..self.size {
+            for line in 0..self.height {
                 if max.is_none() || self.grid[line][column].size > max.unwrap() {
                     max = Some(self.grid[line][column].size);
                     self.grid[line][column].seen = true;
                 }
             }
             max = None;
-            for line in (0..self.size).rev() {
+            for line in (0..self.height).rev() {
                 if max.is_none() || self.grid[line][column].size > max.unwrap() {
                     max = Some(self.grid[line][column].size);
                     self.grid[line][column].seen = true;
@@ -68,45 +106,266 @@ impl Forest {
         self.grid.iter().flatten().filter(|&x| x.seen).count()
     }
 
-    fn scenic_score(&self, line:usize, column: usize) -> usize {
-        let mut result = 1;
-        // Left
-        if column > 0 {
-            let mut lower_trees = (0..column).rev().take_while(|&x| self.grid[line][x].size < self.grid[line][column].size).count() + 1;
-            if column + 1 - lower_trees == 0 {
-                lower_trees -=1;
+    // Naive walk in each direction, kept as a reference for scenic_scores
+    #[cfg(test)]
+    fn scenic_score(&self, line: usize, column: usize) -> usize {
+        let size = self.grid[line][column].size;
+        let viewing_distance = |trees: &mut dyn Iterator<Item = &Tree>| {
+            let mut result = 0;
+            for t in trees {
+                result += 1;
+                if t.size >= size {
+                    break;
+                }
+            }
+            result
+        };
+        let row = &self.grid[line];
+        let col = self.grid.iter().map(|l| &l[column]).collect::<Vec<&Tree>>();
+        viewing_distance(&mut row[..column].iter().rev())
+            * viewing_distance(&mut row[column + 1..].iter())
+            * viewing_distance(&mut col[..line].iter().rev().copied())
+            * viewing_distance(&mut col[line + 1..].iter().copied())
+    }
+
+    // Viewing distance from each tree of a line towards its start : the nearest tree at least as
+    // high is found with a stack of trees of decreasing heights
+    fn viewing_distances(sizes: &[usize]) -> Vec<usize> {
+        let mut stack: Vec<usize> = vec![];
+        let mut result = vec![0; sizes.len()];
+        for (i, size) in sizes.iter().enumerate() {
+            while stack.last().is_some_and(|&j| sizes[j] < *size) {
+                stack.pop();
             }
-            result *= lower_trees;
+            result[i] = i - stack.last().copied().unwrap_or(0);
+            stack.push(i);
         }
-        // Right
-        if column < self.size -1 {
-            let mut lower_trees = (column+1..self.size).take_while(|&x| self.grid[line][x].size < self.grid[line][column].size).count() + 1;
-            if self.size - column - lower_trees == 0 {
-                lower_trees -=1;
+        result
+    }
+
+    fn scenic_scores(&self) -> Vec<Vec<usize>> {
+        let mut scores = vec![vec![1; self.width]; self.height];
+        for (line, scores) in scores.iter_mut().enumerate() {
+            let mut sizes = self.grid[line].iter().map(|t| t.size).collect::<Vec<_>>();
+            for (column, d) in Forest::viewing_distances(&sizes).into_iter().enumerate() {
+                scores[column] *= d;
+            }
+            sizes.reverse();
+            for (column, d) in Forest::viewing_distances(&sizes)
+                .into_iter()
+                .rev()
+                .enumerate()
+            {
+                scores[column] *= d;
             }
-            result *= lower_trees;
         }
-        // Top
-        if line > 0 {
-            let mut lower_trees = (0..line).rev().take_while(|&x| self.grid[x][column].size < self.grid[line][column].size).count() + 1;
-            if line + 1 - lower_trees == 0 {
-                lower_trees -=1;
+        for column in 0..self.width {
+            let mut sizes = self.grid.iter().map(|l| l[column].size).collect::<Vec<_>>();
+            for (line, d) in Forest::viewing_distances(&sizes).into_iter().enumerate() {
+                scores[line][column] *= d;
+            }
+            sizes.reverse();
+            for (line, d) in Forest::viewing_distances(&sizes)
+                .into_iter()
+                .rev()
+                .enumerate()
+            {
+                scores[line][column] *= d;
             }
-            result *= lower_trees;
         }
-        // Bottom
-        if line < self.size - 1 {
-            let mut lower_trees = (line + 1..self.size).take_while(|&x| self.grid[x][column].size < self.grid[line][column].size).count() + 1;
-            if self.size - line - lower_trees == 0 {
-                lower_trees -=1;
+        scores
+    }
+}
+
+impl Forest {
+    fn tree(&self, line: isize, column: isize) -> Option<&Tree> {
+        if line < 0 || column < 0 {
+            return None;
+        }
+        self.grid.get(line as usize)?.get(column as usize)
+    }
+
+    // A tree `k` steps away from the eye hides the top of a tree `n` steps away when it reaches
+    // the line of sight between them
+    fn hides(size: usize, eye: usize, top: usize, k: isize, n: isize) -> bool {
+        let (eye, top) = (eye as f64, top as f64);
+        size as f64 >= eye + (top - eye) * k as f64 / n as f64
+    }
+
+    // Compass directions only restrict where the eye looks, visibility is the one of the rays
+    fn visible_along(
+        &self,
+        viewpoint: Viewpoint,
+        direction: (isize, isize),
+    ) -> Vec<(usize, usize)> {
+        // Enough steps to cross the whole grid from anywhere the viewpoint is
+        let steps = viewpoint.line.unsigned_abs()
+            + viewpoint.column.unsigned_abs()
+            + self.width
+            + self.height;
+        let trees = (1..=steps as isize)
+            .filter_map(|step| {
+                let (line, column) = (
+                    viewpoint.line + step * direction.0,
+                    viewpoint.column + step * direction.1,
+                );
+                self.tree(line, column)
+                    .map(|tree| (step, line as usize, column as usize, tree.size))
+            })
+            .collect::<Vec<_>>();
+        trees
+            .iter()
+            .enumerate()
+            .filter(|(i, &(n, _, _, top))| {
+                trees[..*i]
+                    .iter()
+                    .all(|&(k, _, _, size)| !Forest::hides(size, viewpoint.height, top, k, n))
+            })
+            .map(|(_, &(_, line, column, _))| (line, column))
+            .collect()
+    }
+
+    // The ray follows the digital line between the eye and the tree top, and is blocked by any
+    // tree in between reaching its height
+    fn ray_reaches(&self, viewpoint: Viewpoint, line: usize, column: usize) -> bool {
+        let (dl, dc) = (
+            line as isize - viewpoint.line,
+            column as isize - viewpoint.column,
+        );
+        let n = dl.abs().max(dc.abs());
+        let top = self.grid[line][column].size;
+        (1..n).all(|k| {
+            let t = k as f64 / n as f64;
+            let l = viewpoint.line + (dl as f64 * t).round() as isize;
+            let c = viewpoint.column + (dc as f64 * t).round() as isize;
+            self.tree(l, c)
+                .is_none_or(|tree| !Forest::hides(tree.size, viewpoint.height, top, k, n))
+        })
+    }
+
+    fn visible_from(&self, viewpoint: Viewpoint, sight: Sight) -> HashSet<(usize, usize)> {
+        match sight {
+            Sight::Compass => COMPASS
+                .iter()
+                .flat_map(|&d| self.visible_along(viewpoint, d))
+                .collect(),
+            Sight::Rays => (0..self.height)
+                .flat_map(|l| (0..self.width).map(move |c| (l, c)))
+                .filter(|&(l, c)| (l as isize, c as isize) != (viewpoint.line, viewpoint.column))
+                .filter(|&(l, c)| self.ray_reaches(viewpoint, l, c))
+                .collect(),
+        }
+    }
+}
+
+impl Forest {
+    // Highest scenic score, first found in reading order on ties
+    fn best_spot(&self) -> (usize, usize, usize) {
+        let mut best = (0, 0, 0);
+        for (line, scores) in self.scenic_scores().iter().enumerate() {
+            for (column, &score) in scores.iter().enumerate() {
+                if score > best.2 {
+                    best = (line, column, score);
+                }
+            }
+        }
+        best
+    }
+
+    // One row per tree, seen flags are only set once count_visible_trees has run
+    fn render_csv(&self) -> String {
+        let scores = self.scenic_scores();
+        let mut result = String::from("line,column,size,seen,scenic_score\n");
+        for (line, trees) in self.grid.iter().enumerate() {
+            for (column, tree) in trees.iter().enumerate() {
+                result.push_str(&format!(
+                    "{line},{column},{},{},{}\n",
+                    tree.size, tree.seen, scores[line][column]
+                ));
             }
-            result *= lower_trees;
         }
         result
     }
+
+    // Plain PPM image going from blue (0.0) to red (1.0), one pixel per tree
+    fn render_heatmap(&self, value: impl Fn(usize, usize) -> f64) -> String {
+        let mut result = format!("P3\n{} {}\n255\n", self.width, self.height);
+        for line in 0..self.height {
+            let pixels = (0..self.width)
+                .map(|column| {
+                    let red = (value(line, column).clamp(0.0, 1.0) * 255.0).round() as u8;
+                    format!("{red} 0 {}", 255 - red)
+                })
+                .collect::<Vec<String>>();
+            result.push_str(&pixels.join(" "));
+            result.push('\n');
+        }
+        result
+    }
+
+    fn export_heatmaps(&mut self, dir: &Path) -> io::Result<()> {
+        self.count_visible_trees();
+        let scores = self.scenic_scores();
+        let max = self.best_spot().2.max(1) as f64;
+        fs::create_dir_all(dir)?;
+        fs::write(
+            dir.join("visibility.ppm"),
+            self.render_heatmap(|l, c| if self.grid[l][c].seen { 1.0 } else { 0.0 }),
+        )?;
+        fs::write(
+            dir.join("scenic.ppm"),
+            self.render_heatmap(|l, c| scores[l][c] as f64 / max),
+        )?;
+        fs::write(dir.join("forest.csv"), self.render_csv())
+    }
 }
 
+// Writes visibility.ppm, scenic.ppm and forest.csv, and returns the best tree house spot
+pub fn export_heatmaps(input: &str, dir: &str) -> (usize, usize, usize) {
+    let mut forest = Forest::new(input);
+    forest.export_heatmaps(Path::new(dir)).unwrap();
+    forest.best_spot()
+}
 
+pub fn visible_count(
+    input: &str,
+    line: isize,
+    column: isize,
+    height: usize,
+    sight: Sight,
+) -> usize {
+    let viewpoint = Viewpoint {
+        line,
+        column,
+        height,
+    };
+    Forest::new(input).visible_from(viewpoint, sight).len()
+}
+
+// Visible trees keep their height, hidden ones are shown as '.' and the viewpoint as 'V'
+pub fn render_view(input: &str, line: isize, column: isize, height: usize, sight: Sight) -> String {
+    let forest = Forest::new(input);
+    let viewpoint = Viewpoint {
+        line,
+        column,
+        height,
+    };
+    let visible = forest.visible_from(viewpoint, sight);
+    let mut result = String::new();
+    for (l, trees) in forest.grid.iter().enumerate() {
+        for (c, tree) in trees.iter().enumerate() {
+            if (l as isize, c as isize) == (line, column) {
+                result.push('V');
+            } else if visible.contains(&(l, c)) {
+                result.push_str(&tree.size.to_string());
+            } else {
+                result.push('.');
+            }
+        }
+        result.push('\n');
+    }
+    result
+}
 
 pub fn part_1(input: &str) -> usize {
     let mut forest = Forest::new(input);
@@ -115,13 +374,7 @@ pub fn part_1(input: &str) -> usize {
 
 pub fn part_2(input: &str) -> usize {
     let forest = Forest::new(input);
-    let mut scenic_scores = vec![];
-    for i in 1..forest.size-1 {
-        for j in 1..forest.size-1 {
-            scenic_scores.push(forest.scenic_score(i, j));
-        }
-    }
-    *scenic_scores.iter().max().unwrap()
+    forest.best_spot().2
 }
 
 #[cfg(test)]
@@ -141,19 +394,133 @@ mod tests {
     }
 
     #[test]
-    fn test_scenic_view()
-    {
+    fn test_scenic_view() {
         let forest = Forest::new(TEST_INPUT);
         assert_eq!(forest.scenic_score(1, 2), 4);
     }
 
     #[test]
-    fn test_scenic_view_bis()
-    {
+    fn test_scenic_view_bis() {
         let forest = Forest::new(TEST_INPUT);
         assert_eq!(forest.scenic_score(3, 2), 8);
     }
 
+    #[test]
+    fn test_rectangular_forest() {
+        let input = "30373\n25512\n65332";
+        assert_eq!(part_1(input), 14);
+        let forest = Forest::new(input);
+        assert_eq!(forest.scenic_scores()[1][2], 2);
+        assert_eq!(part_2(input), 2);
+        assert_eq!(part_1("1\n2\n1"), 3);
+        assert_eq!(part_2("3"), 0);
+    }
+
+    #[test]
+    fn test_scenic_scores_against_naive() {
+        // Pseudo-random forests built from a linear congruential generator
+        let mut seed: usize = 1;
+        let mut random = |max: usize| {
+            seed = (seed * 1103515245 + 12345) % (1 << 31);
+            seed % max
+        };
+        for _ in 0..200 {
+            let (width, height, max_size) = (1 + random(12), 1 + random(12), 1 + random(10));
+            let input = (0..height)
+                .map(|_| (0..width).map(|_| random(max_size).to_string()).collect())
+                .collect::<Vec<String>>()
+                .join("\n");
+            let forest = Forest::new(&input);
+            let scores = forest.scenic_scores();
+            for (line, scores) in scores.iter().enumerate() {
+                for (column, score) in scores.iter().enumerate() {
+                    assert_eq!(*score, forest.scenic_score(line, column), "{input}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compass_matches_rays() {
+        let forest = Forest::new(TEST_INPUT);
+        for line in -2..7 {
+            for column in -2..7 {
+                for height in [0, 3, 5, 9, 20] {
+                    let viewpoint = Viewpoint {
+                        line,
+                        column,
+                        height,
+                    };
+                    let rays = forest.visible_from(viewpoint, Sight::Rays);
+                    for direction in COMPASS {
+                        // Trees seen along a direction are the ones seen by rays on that line
+                        let mut along = forest.visible_along(viewpoint, direction);
+                        let mut on_line = rays
+                            .iter()
+                            .copied()
+                            .filter(|&(l, c)| {
+                                let (dl, dc) = (l as isize - line, c as isize - column);
+                                let n = dl.abs().max(dc.abs());
+                                (dl, dc) == (direction.0 * n, direction.1 * n)
+                            })
+                            .collect::<Vec<_>>();
+                        along.sort();
+                        on_line.sort();
+                        assert_eq!(along, on_line);
+                    }
+                }
+            }
+        }
+        // The 4 next to the eye hides the lower trees behind it in both modes
+        for sight in [Sight::Compass, Sight::Rays] {
+            assert_eq!(render_view("40000", 0, -1, 5, sight), "4....\n");
+        }
+    }
+
+    #[test]
+    fn test_visible_from() {
+        assert_eq!(visible_count(TEST_INPUT, 2, 2, 0, Sight::Compass), 9);
+        // The 5 next to the eye reaches the line of sight towards the 6
+        assert_eq!(
+            render_view(TEST_INPUT, 2, 2, 0, Sight::Compass),
+            "....3\n.551.\n.5V3.\n.354.\n.....\n"
+        );
+        // High enough, every tree can be seen from the outside
+        assert_eq!(visible_count(TEST_INPUT, -1, -1, 100, Sight::Rays), 25);
+        assert_eq!(visible_count(TEST_INPUT, -1, -1, 100, Sight::Compass), 5);
+        // Neighbours are never hidden, but the 7 blocks the ray towards the corner
+        let view = render_view(TEST_INPUT, -1, 2, 0, Sight::Rays);
+        assert_eq!(&view[..5], "3037.");
+    }
+
+    #[test]
+    fn test_best_spot() {
+        assert_eq!(Forest::new(TEST_INPUT).best_spot(), (3, 2, 8));
+        assert_eq!(Forest::new("00\n00").best_spot(), (0, 0, 0));
+    }
+
+    #[test]
+    fn test_export_heatmaps() {
+        let dir = std::env::temp_dir().join(format!("day8-heatmaps-{}", std::process::id()));
+        let mut forest = Forest::new("303\n255\n653");
+        forest.export_heatmaps(&dir).unwrap();
+        let visibility = fs::read_to_string(dir.join("visibility.ppm")).unwrap();
+        let scenic = fs::read_to_string(dir.join("scenic.ppm")).unwrap();
+        let csv = fs::read_to_string(dir.join("forest.csv")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            visibility,
+            "P3\n3 3\n255\n255 0 0 255 0 0 255 0 0\n255 0 0 255 0 0 255 0 0\n255 0 0 255 0 0 255 0 0\n"
+        );
+        assert_eq!(
+            scenic,
+            "P3\n3 3\n255\n0 0 255 0 0 255 0 0 255\n0 0 255 255 0 0 0 0 255\n0 0 255 0 0 255 0 0 255\n"
+        );
+        assert_eq!(csv.lines().count(), 10);
+        assert_eq!(csv.lines().nth(5), Some("1,1,5,true,1"));
+        assert_eq!(csv.lines().nth(1), Some("0,0,3,true,0"));
+    }
+
     #[test]
     fn test_part_2() {
         // Adjust part 2 test to match provided challenge example
@@ -3,6 +3,36 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| args.iter().any(|x| x == name);
+    if let Some(i) = args.iter().position(|x| x == "--heatmap") {
+        let dir = args
+            .get(i + 1)
+            .unwrap_or_else(|| panic!("Missing value for --heatmap"));
+        let (line, column, score) = export_heatmaps(input, dir);
+        println!("Heatmaps written to {dir}");
+        println!("Best tree house spot : line {line}, column {column} with a score of {score}");
+    } else if let Some(i) = args.iter().position(|x| x == "--from") {
+        // --from LINE COLUMN HEIGHT, the viewpoint may be outside of the forest
+        let value = |offset: usize| {
+            args.get(i + offset)
+                .unwrap_or_else(|| panic!("Usage: --from LINE COLUMN HEIGHT [--rays]"))
+        };
+        let line = value(1).parse::<isize>().expect("Invalid line");
+        let column = value(2).parse::<isize>().expect("Invalid column");
+        let height = value(3).parse::<usize>().expect("Invalid height");
+        let sight = if flag("--rays") {
+            Sight::Rays
+        } else {
+            Sight::Compass
+        };
+        print!("{}", render_view(input, line, column, height, sight));
+        println!(
+            "{} visible trees",
+            visible_count(input, line, column, height, sight)
+        );
+    } else {
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+    }
 }
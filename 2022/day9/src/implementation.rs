@@ -1,33 +1,33 @@
-use std::collections::HashSet;
-
-#[derive(PartialEq, Eq, Hash, Copy, Clone)]
-struct Coord(isize, isize);
-
-impl Coord {
-    fn move_coord(&mut self, movement: Move) {
-        match movement {
-            Move::Up => self.1 += 1,
-            Move::Down => self.1 -= 1,
-            Move::Left => self.0 -= 1,
-            Move::Right => self.0 += 1,
-            Move::UpRight => {
-                self.0 += 1;
-                self.1 += 1
-            }
-            Move::UpLeft => {
-                self.0 -= 1;
-                self.1 += 1
-            }
-            Move::DownRight => {
-                self.0 += 1;
-                self.1 -= 1
-            }
-            Move::DownLeft => {
-                self.0 -= 1;
-                self.1 -= 1
-            }
+use std::{array, collections::HashSet, fs, io, path::Path};
+
+// Position in a space of N dimensions, the first axes being x, y then z
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+struct Coord<const N: usize>([isize; N]);
+
+impl<const N: usize> Coord<N> {
+    fn origin() -> Self {
+        Coord([0; N])
+    }
+
+    fn move_coord(&mut self, delta: Coord<N>) {
+        for (a, d) in self.0.iter_mut().zip(delta.0) {
+            *a += d;
         }
     }
+
+    fn distance(&self, other: &Coord<N>) -> usize {
+        self.0
+            .iter()
+            .zip(other.0)
+            .map(|(a, b)| a.abs_diff(b))
+            .max()
+            .unwrap_or(0)
+    }
+
+    // One step towards the target along every axis where they differ
+    fn step_towards(&self, target: &Coord<N>) -> Coord<N> {
+        Coord(array::from_fn(|i| (target.0[i] - self.0[i]).signum()))
+    }
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -40,6 +40,8 @@ enum Move {
     UpLeft,
     DownRight,
     DownLeft,
+    Forward,
+    Backward,
 }
 
 #[derive(Debug)]
@@ -47,88 +49,129 @@ struct Instruction {
     movement: Move,
     count: usize,
 }
-struct Grid {
-    knots: Vec<Coord>,
-    visited: Vec<HashSet<Coord>>,
+impl Move {
+    fn parse(value: &str) -> Move {
+        match value {
+            "U" => Move::Up,
+            "D" => Move::Down,
+            "L" => Move::Left,
+            "R" => Move::Right,
+            "UR" | "RU" => Move::UpRight,
+            "UL" | "LU" => Move::UpLeft,
+            "DR" | "RD" => Move::DownRight,
+            "DL" | "LD" => Move::DownLeft,
+            "F" => Move::Forward,
+            "B" => Move::Backward,
+            _ => panic!("Unexpected move {value}"),
+        }
+    }
+
+    fn is_3d(&self) -> bool {
+        matches!(self, Move::Forward | Move::Backward)
+    }
+
+    fn delta<const N: usize>(&self) -> Coord<N> {
+        let (x, y, z) = match self {
+            Move::Up => (0, 1, 0),
+            Move::Down => (0, -1, 0),
+            Move::Left => (-1, 0, 0),
+            Move::Right => (1, 0, 0),
+            Move::UpRight => (1, 1, 0),
+            Move::UpLeft => (-1, 1, 0),
+            Move::DownRight => (1, -1, 0),
+            Move::DownLeft => (-1, -1, 0),
+            Move::Forward => (0, 0, 1),
+            Move::Backward => (0, 0, -1),
+        };
+        Coord(array::from_fn(|i| [x, y, z].get(i).copied().unwrap_or(0)))
+    }
+}
+
+// Smallest box holding every position reached by the rope, coordinates being signed
+#[derive(Default, Debug, PartialEq, Clone, Copy)]
+struct Bounds {
+    x_min: isize,
+    x_max: isize,
+    y_min: isize,
+    y_max: isize,
+}
+
+impl Bounds {
+    // Only the x and y axes are drawn
+    fn expand<const N: usize>(&mut self, c: Coord<N>) {
+        self.x_min = self.x_min.min(c.0[0]);
+        self.x_max = self.x_max.max(c.0[0]);
+        self.y_min = self.y_min.min(c.0[1]);
+        self.y_max = self.y_max.max(c.0[1]);
+    }
+}
+
+enum FrameFormat {
+    Text,
+    Pbm,
+}
+
+struct Grid<const N: usize = 2> {
+    knots: Vec<Coord<N>>,
+    visited: Vec<HashSet<Coord<N>>>,
     moves: Vec<Instruction>,
+    // Distance a knot may drift from the previous one before being pulled
+    slack: usize,
+    bounds: Bounds,
 }
 
-impl Grid {
+impl<const N: usize> Grid<N> {
     const KNOTS_COUNT: usize = 10;
-    fn new(input: &str) -> Self {
+    fn new(input: &str, knots_count: usize) -> Self {
+        assert!(knots_count > 0);
+        assert!(N >= 2);
+        let moves = input
+            .lines()
+            .map(|x| x.split_ascii_whitespace().collect::<Vec<&str>>())
+            .map(|x| Instruction {
+                movement: Move::parse(x[0]),
+                count: x[1].parse::<usize>().unwrap(),
+            })
+            .collect::<Vec<Instruction>>();
+        if N < 3 && moves.iter().any(|x| x.movement.is_3d()) {
+            panic!("Moves along the z axis need a rope in 3 dimensions");
+        }
         Grid {
-            knots: vec![Coord(0, 0); Grid::KNOTS_COUNT],
-            visited: vec![HashSet::from([Coord(0, 0)]); Grid::KNOTS_COUNT],
-            moves: input
-                .lines()
-                .map(|x| x.split_ascii_whitespace().collect::<Vec<&str>>())
-                .map(|x| match x[0] {
-                    "U" => Instruction {
-                        movement: Move::Up,
-                        count: x[1].parse::<usize>().unwrap(),
-                    },
-                    "D" => Instruction {
-                        movement: Move::Down,
-                        count: x[1].parse::<usize>().unwrap(),
-                    },
-                    "L" => Instruction {
-                        movement: Move::Left,
-                        count: x[1].parse::<usize>().unwrap(),
-                    },
-                    "R" => Instruction {
-                        movement: Move::Right,
-                        count: x[1].parse::<usize>().unwrap(),
-                    },
-                    _ => panic!("Unexpected move char {}", x[0]),
-                })
-                .collect::<Vec<Instruction>>(),
+            knots: vec![Coord::origin(); knots_count],
+            visited: vec![HashSet::from([Coord::origin()]); knots_count],
+            moves,
+            slack: 1,
+            bounds: Bounds::default(),
         }
     }
 
-    fn get_knot_move(&self, i: usize) -> Option<Move> {
+    fn get_knot_move(&self, i: usize) -> Option<Coord<N>> {
         // Tail is still close enough to head : no need to move
         assert!(i > 0);
-        if self.knots[i - 1].0.abs_diff(self.knots[i].0) <= 1
-            && self.knots[i - 1].1.abs_diff(self.knots[i].1) <= 1
-        {
+        if self.knots[i - 1].distance(&self.knots[i]) <= self.slack {
             return None;
         }
-        if self.knots[i - 1].0 > self.knots[i].0 && self.knots[i - 1].1 > self.knots[i].1 {
-            return Some(Move::UpRight);
-        }
-        if self.knots[i - 1].0 < self.knots[i].0 && self.knots[i - 1].1 > self.knots[i].1 {
-            return Some(Move::UpLeft);
-        }
-        if self.knots[i - 1].0 > self.knots[i].0 && self.knots[i - 1].1 < self.knots[i].1 {
-            return Some(Move::DownRight);
-        }
-        if self.knots[i - 1].0 < self.knots[i].0 && self.knots[i - 1].1 < self.knots[i].1 {
-            return Some(Move::DownLeft);
-        }
-        if self.knots[i - 1].0 > self.knots[i].0 {
-            return Some(Move::Right);
-        }
-        if self.knots[i - 1].0 < self.knots[i].0 {
-            return Some(Move::Left);
-        }
-        if self.knots[i - 1].1 > self.knots[i].1 {
-            return Some(Move::Up);
-        }
-        if self.knots[i - 1].1 < self.knots[i].1 {
-            return Some(Move::Down);
-        }
-        None
+        Some(self.knots[i].step_towards(&self.knots[i - 1]))
     }
     fn run_moves(&mut self) {
-        for m in &self.moves[..] {
-            for _ in 0..m.count {
-                self.knots[0].move_coord(m.movement);
-                for i in 1..Grid::KNOTS_COUNT {
+        self.run_moves_with(|_, _| {});
+    }
+
+    // The callback is given the grid after each step, and whether the step ends an instruction
+    fn run_moves_with(&mut self, mut on_step: impl FnMut(&Grid<N>, bool)) {
+        for k in 0..self.moves.len() {
+            let (movement, count) = (self.moves[k].movement, self.moves[k].count);
+            for step in 0..count {
+                self.knots[0].move_coord(movement.delta());
+                self.visited[0].insert(self.knots[0]);
+                self.bounds.expand(self.knots[0]);
+                for i in 1..self.knots.len() {
                     if let Some(m) = self.get_knot_move(i) {
                         self.knots[i].move_coord(m);
                         self.visited[i].insert(self.knots[i]);
                     }
                 }
+                on_step(self, step == count - 1);
             }
         }
     }
@@ -138,14 +181,149 @@ impl Grid {
     }
 }
 
+impl Grid<2> {
+    fn knot_label(&self, i: usize) -> char {
+        match i {
+            0 => 'H',
+            _ if self.knots.len() == 2 => 'T',
+            _ => char::from_digit(i as u32, 36).unwrap_or('*'),
+        }
+    }
+
+    // Same drawing as the puzzle statement : the knots over the starting point, or the positions
+    // visited by a given knot
+    fn render(&self, visited_knot: Option<usize>) -> String {
+        let mut result = vec![];
+        for y in (self.bounds.y_min..=self.bounds.y_max).rev() {
+            let mut line = String::new();
+            for x in self.bounds.x_min..=self.bounds.x_max {
+                let c = Coord([x, y]);
+                let knot = self.knots.iter().position(|k| *k == c);
+                line.push(match (visited_knot, knot) {
+                    (None, Some(i)) => self.knot_label(i),
+                    _ if c == Coord::origin() => 's',
+                    (Some(i), _) if self.visited[i].contains(&c) => '#',
+                    _ => '.',
+                });
+            }
+            result.push(line);
+        }
+        result.join("\n")
+    }
+
+    fn render_pbm(&self) -> String {
+        let drawing = self.render(None);
+        let pixels = drawing
+            .lines()
+            .map(|l| {
+                l.chars()
+                    .map(|c| if c == '.' { "0" } else { "1" })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>();
+        format!(
+            "P1\n{} {}\n{}\n",
+            self.bounds.x_max - self.bounds.x_min + 1,
+            self.bounds.y_max - self.bounds.y_min + 1,
+            pixels.join("\n")
+        )
+    }
+
+    // Writes the initial state then a frame after each step, or after each instruction. Returns
+    // the number of frames written
+    fn export_frames(
+        &mut self,
+        dir: &Path,
+        per_step: bool,
+        format: FrameFormat,
+    ) -> io::Result<usize> {
+        fs::create_dir_all(dir)?;
+        let write_frame = |grid: &Grid<2>, index: usize| match format {
+            FrameFormat::Text => fs::write(
+                dir.join(format!("frame_{index:05}.txt")),
+                grid.render(None) + "\n",
+            ),
+            FrameFormat::Pbm => {
+                fs::write(dir.join(format!("frame_{index:05}.pbm")), grid.render_pbm())
+            }
+        };
+        write_frame(self, 0)?;
+        let mut frames = 1;
+        let mut result = Ok(());
+        self.run_moves_with(|grid, end| {
+            if result.is_ok() && (per_step || end) {
+                result = write_frame(grid, frames);
+                frames += 1;
+            }
+        });
+        result.map(|_| frames)
+    }
+}
+
+fn count_visited_by_knot<const N: usize>(
+    input: &str,
+    knots_count: usize,
+    slack: usize,
+) -> Vec<usize> {
+    let mut grid = Grid::<N>::new(input, knots_count);
+    grid.slack = slack;
+    grid.run_moves();
+    (0..knots_count).map(|i| grid.count_visited(i)).collect()
+}
+
+// Number of positions visited by each knot of the rope, starting from the head. The rope moves in
+// 3 dimensions as soon as the input has moves along the z axis
+pub fn visited_counts(input: &str, knots_count: usize, slack: usize) -> Vec<usize> {
+    if input
+        .lines()
+        .any(|x| x.starts_with('F') || x.starts_with('B'))
+    {
+        count_visited_by_knot::<3>(input, knots_count, slack)
+    } else {
+        count_visited_by_knot::<2>(input, knots_count, slack)
+    }
+}
+
+// Final position of the knots, then the positions visited by the tail
+pub fn render(input: &str, knots_count: usize, slack: usize) -> String {
+    let mut grid = Grid::<2>::new(input, knots_count);
+    grid.slack = slack;
+    grid.run_moves();
+    format!(
+        "{}\n\n{}",
+        grid.render(None),
+        grid.render(Some(knots_count - 1))
+    )
+}
+
+pub fn export_frames(
+    input: &str,
+    knots_count: usize,
+    slack: usize,
+    dir: &str,
+    per_step: bool,
+    pbm: bool,
+) -> usize {
+    let mut grid = Grid::<2>::new(input, knots_count);
+    grid.slack = slack;
+    let format = if pbm {
+        FrameFormat::Pbm
+    } else {
+        FrameFormat::Text
+    };
+    grid.export_frames(Path::new(dir), per_step, format)
+        .unwrap()
+}
+
 pub fn part_1(input: &str) -> usize {
-    let mut grid = Grid::new(input);
+    let mut grid = Grid::<2>::new(input, Grid::<2>::KNOTS_COUNT);
     grid.run_moves();
     grid.count_visited(1)
 }
 
 pub fn part_2(input: &str) -> usize {
-    let mut grid = Grid::new(input);
+    let mut grid = Grid::<2>::new(input, Grid::<2>::KNOTS_COUNT);
     grid.run_moves();
     grid.count_visited(9)
 }
@@ -173,6 +351,66 @@ D 10
 L 25
 U 20";
 
+    const TEST_OUTPUT_KNOTS: &str = "\
+......
+......
+.TH...
+......
+s.....";
+
+    const TEST_OUTPUT_VISITED: &str = "\
+..##..
+...##.
+.####.
+....#.
+s###..";
+
+    const TEST_LARGER_OUTPUT_KNOTS: &str = "\
+H.........................
+1.........................
+2.........................
+3.........................
+4.........................
+5.........................
+6.........................
+7.........................
+8.........................
+9.........................
+..........................
+..........................
+..........................
+..........................
+..........................
+...........s..............
+..........................
+..........................
+..........................
+..........................
+..........................";
+
+    const TEST_LARGER_OUTPUT_VISITED: &str = "\
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+..........................
+#.........................
+#.............###.........
+#............#...#........
+.#..........#.....#.......
+..#..........#.....#......
+...#........#.......#.....
+....#......s.........#....
+.....#..............#.....
+......#............#......
+.......#..........#.......
+........#........#........
+.........########.........";
+
     #[test_case(1, 1, Move::Up, 1, 2; "up")]
     #[test_case(1, 1, Move::Down, 1, 0; "down")]
     #[test_case(1, 1, Move::Left, 0, 1; "left")]
@@ -182,10 +420,140 @@ U 20";
     #[test_case(1, 1, Move::DownLeft, 0, 0; "down-left")]
     #[test_case(1, 1, Move::DownRight, 2, 0; "down-right")]
     fn test_move_coord(old_x: isize, old_y: isize, movement: Move, new_x: isize, new_y: isize) {
-        let mut coord = Coord(old_x, old_y);
-        coord.move_coord(movement);
-        assert_eq!(coord.0, new_x);
-        assert_eq!(coord.1, new_y);
+        let mut coord = Coord([old_x, old_y]);
+        coord.move_coord(movement.delta());
+        assert_eq!(coord.0[0], new_x);
+        assert_eq!(coord.0[1], new_y);
+    }
+
+    #[test_case("UR", Move::UpRight; "up-right")]
+    #[test_case("LU", Move::UpLeft; "up-left")]
+    #[test_case("DR", Move::DownRight; "down-right")]
+    #[test_case("DL", Move::DownLeft; "down-left")]
+    fn test_parse_diagonal(value: &str, movement: Move) {
+        assert_eq!(format!("{:?}", Move::parse(value)), format!("{movement:?}"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected move X")]
+    fn test_parse_invalid() {
+        Move::parse("X");
+    }
+
+    #[test]
+    fn test_visited_counts() {
+        let counts = visited_counts(TEST_LARGER_INPUT, 10, 1);
+        assert_eq!(counts.len(), 10);
+        assert_eq!(counts[1], part_1(TEST_LARGER_INPUT));
+        assert_eq!(counts[9], 36);
+        assert!(counts.windows(2).all(|x| x[0] >= x[1]));
+        // Knots behind the head do not change its path, nor the path of the knots before them
+        assert_eq!(visited_counts(TEST_LARGER_INPUT, 3, 1)[..], counts[..3]);
+    }
+
+    #[test]
+    fn test_slack() {
+        assert_eq!(visited_counts("R 4", 2, 1), vec![5, 4]);
+        assert_eq!(visited_counts("R 4", 2, 2), vec![5, 3]);
+        assert_eq!(visited_counts("R 4", 2, 4), vec![5, 1]);
+        assert_eq!(visited_counts("R 5\nL 2", 3, 2), vec![6, 4, 2]);
+    }
+
+    #[test]
+    fn test_diagonal_head() {
+        // The tail follows the diagonal right behind the head
+        assert_eq!(visited_counts("UR 4", 2, 1), vec![5, 4]);
+        assert_eq!(visited_counts("UR 4\nDL 4", 2, 1), vec![5, 4]);
+        assert_eq!(visited_counts("UR 3", 4, 1), vec![4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_step_towards() {
+        let tail = Coord([0, 0, 0]);
+        assert_eq!(tail.step_towards(&Coord([2, -1, 0])), Coord([1, -1, 0]));
+        assert_eq!(tail.step_towards(&Coord([1, 1, -2])), Coord([1, 1, -1]));
+        assert_eq!(tail.distance(&Coord([1, 1, -2])), 2);
+    }
+
+    #[test]
+    fn test_3d_rope() {
+        assert_eq!(visited_counts("F 4", 2, 1), vec![5, 4]);
+        assert_eq!(visited_counts("R 1\nU 1\nF 1", 2, 1), vec![4, 1]);
+        assert_eq!(visited_counts("R 1\nU 1\nF 2", 2, 1), vec![5, 2]);
+        let mut grid = Grid::<3>::new("R 1\nU 1\nF 2", 2);
+        grid.run_moves();
+        assert_eq!(grid.knots, vec![Coord([1, 1, 2]), Coord([1, 1, 1])]);
+    }
+
+    #[test_case(TEST_INPUT; "Small grid")]
+    #[test_case(TEST_LARGER_INPUT; "Large grid")]
+    fn test_3d_plane(input: &str) {
+        // The same moves in the x-z plane visit as many positions as in the x-y plane
+        let rotated = input.replace('U', "F").replace('D', "B");
+        assert_eq!(
+            visited_counts(&rotated, 10, 1),
+            visited_counts(input, 10, 1)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Moves along the z axis need a rope in 3 dimensions")]
+    fn test_3d_moves_in_2d() {
+        Grid::<2>::new("F 1", 2);
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(TEST_INPUT, 2, 1),
+            format!("{TEST_OUTPUT_KNOTS}\n\n{TEST_OUTPUT_VISITED}")
+        );
+        assert_eq!(
+            render(TEST_LARGER_INPUT, 10, 1),
+            format!("{TEST_LARGER_OUTPUT_KNOTS}\n\n{TEST_LARGER_OUTPUT_VISITED}")
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut grid = Grid::<2>::new("L 3\nD 2\nUR 6", 2);
+        grid.run_moves();
+        assert_eq!(
+            grid.bounds,
+            Bounds {
+                x_min: -3,
+                x_max: 3,
+                y_min: -2,
+                y_max: 4
+            }
+        );
+        assert_eq!(grid.render(None).lines().count(), 7);
+        assert!(grid.render(None).lines().all(|l| l.len() == 7));
+    }
+
+    #[test]
+    fn test_export_frames() {
+        let dir = std::env::temp_dir().join(format!("day9-frames-{}", std::process::id()));
+        let mut grid = Grid::<2>::new(TEST_INPUT, 2);
+        assert_eq!(
+            grid.export_frames(&dir, true, FrameFormat::Text).unwrap(),
+            25
+        );
+        let last = fs::read_to_string(dir.join("frame_00024.txt")).unwrap();
+        assert_eq!(last, format!("{TEST_OUTPUT_KNOTS}\n"));
+        let first = fs::read_to_string(dir.join("frame_00000.txt")).unwrap();
+        assert_eq!(first, "H\n");
+        let mut grid = Grid::<2>::new(TEST_INPUT, 2);
+        assert_eq!(
+            grid.export_frames(&dir, false, FrameFormat::Pbm).unwrap(),
+            9
+        );
+        let last = fs::read_to_string(dir.join("frame_00008.pbm")).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            last,
+            "P1\n6 5\n0 0 0 0 0 0\n0 0 0 0 0 0\n0 1 1 0 0 0\n0 0 0 0 0 0\n1 0 0 0 0 0\n"
+        );
     }
 
     #[test]
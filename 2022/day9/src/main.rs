@@ -3,6 +3,40 @@ use implementation::*;
 
 fn main() {
     let input = include_str!("input.txt");
-    println!("Part 1 answer : {}", part_1(input));
-    println!("Part 2 answer : {}", part_2(input));
+    let args = std::env::args().collect::<Vec<_>>();
+    let flag = |name: &str| args.iter().any(|x| x == name);
+    let value = |name: &str| {
+        args.iter().position(|x| x == name).map(|i| {
+            args.get(i + 1)
+                .unwrap_or_else(|| panic!("Missing value for {name}"))
+        })
+    };
+    let option = |name: &str| {
+        value(name).map(|x| {
+            x.parse::<usize>()
+                .unwrap_or_else(|_| panic!("Invalid value for {name}"))
+        })
+    };
+    let knots = option("--knots").unwrap_or(10);
+    let slack = option("--slack").unwrap_or(1);
+    if let Some(dir) = value("--frames") {
+        let frames = export_frames(
+            input,
+            knots,
+            slack,
+            dir,
+            !flag("--per-instruction"),
+            flag("--pbm"),
+        );
+        println!("{frames} frames written to {dir}");
+    } else if flag("--render") {
+        println!("{}", render(input, knots, slack));
+    } else if flag("--knots") {
+        for (i, count) in visited_counts(input, knots, slack).into_iter().enumerate() {
+            println!("Knot {i} visited {count} positions");
+        }
+    } else {
+        println!("Part 1 answer : {}", part_1(input));
+        println!("Part 2 answer : {}", part_2(input));
+    }
 }